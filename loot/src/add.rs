@@ -1,38 +1,280 @@
+use pep440_rs::Version;
 use std::path::PathBuf;
+use std::str::FromStr;
 
-use crate::app::AppExternal;
-use crate::utils::create_file_with_content;
-use crate::versions::{get_versions_of_package, version_exists};
+use crate::app::{AppExternal, Bound, Config, Dependency};
+use crate::cache::Cache;
+use crate::error::{Context, LootError};
+use crate::import::requirement_line;
+use crate::lock::Lock;
+use crate::remove::remove_requirement;
+use crate::requirements::RequirementEntry;
+use crate::run::handle_incorrect_config;
+use crate::sync::sync_dependencies;
+use crate::utils::{create_file_with_content, normalize_name};
+use crate::versions::{
+    bounded_requirement, get_versions_of_package, parse_requirement, version_exists,
+};
 
-pub async fn add_dependency(package: &String, version: &Option<String>, mut app: AppExternal<'_>) {
-    let version_to_add = match version {
-        Some(version) => {
-            if version_exists(package, version).await {
-                version.to_owned()
-            } else {
-                panic!("Version does not exist");
-            }
+/// A package given to `loot add`
+enum Requested {
+    /// Looked up in an index
+    Index(RequirementEntry),
+    /// A direct reference `name @ url`, written to lootbox.toml as it is
+    Direct(String, Dependency),
+}
+
+impl Requested {
+    fn name(&self) -> &String {
+        match self {
+            Requested::Index(entry) => &entry.name,
+            Requested::Direct(name, _) => name,
         }
-        None => {
-            let versions = get_versions_of_package(package).await;
-            let version = versions.iter().max().expect("Dependency has no versions");
-            version.to_owned()
+    }
+}
+
+/// Either a PEP 508 requirement, `uvicorn[standard]>=0.30; python_version < '3.13'`, a direct reference,
+/// `app @ https://example.com/app-1.0-py3-none-any.whl`, or a package with an inline version requirement,
+/// `requests@">=2.31"`
+fn requested_package(argument: &str, version: &Option<String>) -> Result<Requested, LootError> {
+    // Only direct references have a url
+    if argument.contains("://") {
+        if version.is_some() {
+            return Err(LootError::config(format!(
+                "{} is a direct reference, it has no version",
+                argument
+            )));
+        }
+        let (name, dependency) = requirement_line(argument).map_err(|error| {
+            LootError::config(format!("Invalid requirement {}: {}", argument, error))
+        })?;
+        return Ok(Requested::Direct(name, dependency));
+    }
+
+    let entry = match RequirementEntry::parse(argument) {
+        Ok(entry) => entry,
+        Err(error) => {
+            let Some((name, requirement)) = argument.split_once('@') else {
+                return Err(LootError::config(format!(
+                    "Invalid requirement {}: {}",
+                    argument, error
+                )));
+            };
+            if version.is_some() {
+                return Err(LootError::config("The version was given twice")
+                    .with_hint("Use either package@version or --version"));
+            }
+            return Ok(Requested::Index(RequirementEntry {
+                name: name.trim().to_owned(),
+                version: Some(requirement.trim().to_owned()),
+                extras: Vec::new(),
+                markers: None,
+            }));
         }
     };
+    match (&entry.version, version) {
+        (Some(_), Some(_)) => Err(LootError::config(format!(
+            "The version of {} was given twice",
+            entry.name
+        ))),
+        (None, Some(version)) => Ok(Requested::Index(RequirementEntry {
+            version: Some(version.clone()),
+            ..entry
+        })),
+        _ => Ok(Requested::Index(entry)),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn add_dependency(
+    packages: &[String],
+    version: &Option<String>,
+    group: &Option<String>,
+    index: &Option<String>,
+    pre: &bool,
+    bound: &Option<Bound>,
+    sync: &bool,
+    mut app: AppExternal<'_>,
+) -> Result<(), LootError> {
+    if version.is_some() && packages.len() > 1 {
+        return Err(
+            LootError::config("--version can only be used when adding a single package")
+                .with_hint("Give each version with package@version instead"),
+        );
+    }
+    let requested = packages
+        .iter()
+        .map(|package| requested_package(package, version))
+        .collect::<Result<Vec<Requested>, LootError>>()?;
+    for (position, package) in requested.iter().enumerate() {
+        if requested[..position]
+            .iter()
+            .any(|other| normalize_name(other.name()) == normalize_name(package.name()))
+        {
+            return Err(LootError::config(format!(
+                "{} was given twice",
+                package.name()
+            )));
+        }
+    }
 
-    app.make_internal(None).await;
+    app.make_internal(None).await?;
+    let mut config = app.app_config.clone().expect("Config file does not exist");
+
+    // Every package is checked before anything is written
+    for package in requested {
+        let package = match package {
+            Requested::Index(package) => package,
+            Requested::Direct(name, dependency) => {
+                if index.is_some() {
+                    return Err(LootError::config(format!(
+                        "{} is a direct reference, it does not come from an index",
+                        name
+                    )));
+                }
+                let requirements = config.group_requirements_mut(group);
+                let name = remove_requirement(requirements, &name).map_or(name, |(key, _)| key);
+                requirements.insert(name, dependency);
+                continue;
+            }
+        };
+        let index_url = match index {
+            Some(index) => config
+                .get_index(index)?
+                .url
+                .trim_end_matches('/')
+                .to_owned(),
+            None => config.index_for(&package.name)?,
+        };
+        let version_to_add = version_to_add(
+            &package.name,
+            package.version,
+            &index_url,
+            pre,
+            bound,
+            &config,
+            &app.cache,
+        )
+        .await?;
+
+        // Extras and markers of an existing requirement are kept unless new ones are given. It keeps the name it has
+        // in lootbox.toml however the package was written
+        let requirements = config.group_requirements_mut(group);
+        let (name, mut dependency) = match remove_requirement(requirements, &package.name) {
+            Some((key, dependency)) => (key, dependency.with_version(version_to_add)),
+            None => (package.name, Dependency::Version(version_to_add)),
+        };
+        if !package.extras.is_empty() {
+            dependency = dependency.with_extras(package.extras);
+        }
+        if let Some(markers) = package.markers {
+            dependency = dependency.with_markers(markers);
+        }
+        if let Some(index) = index {
+            dependency = dependency.with_index(index.to_owned());
+        }
+        requirements.insert(name, dependency);
+    }
 
-    app.app_config
-        .as_mut()
-        .expect("Config file does not exist")
-        .requirements
-        .insert(package.to_owned(), version_to_add);
+    // A conflict exits before lootbox.toml is touched
+    println!("Resolving dependencies");
+    let preferred = Lock::read(None)?
+        .map(|lock| lock.packages)
+        .unwrap_or_default();
+    let lock = Lock::resolve_preferring(&config, &app, &preferred).await?;
 
     create_file_with_content(
         &PathBuf::from(crate::DEPENDENCIES_FILE),
-        toml::to_string_pretty(&app.app_config.expect("Config file does not exist"))
+        toml::to_string_pretty(&config)
             .expect("Could not convert to toml")
             .as_bytes(),
     )
-    .expect("Error writing to config file");
+    .context(|| LootError::config("Error writing to lootbox.toml"))?;
+    lock.write(None)?;
+
+    if *sync {
+        return sync_dependencies(&false, &false, &[], &false, app)
+            .await
+            .map(|_| ());
+    }
+    // Only what changed is installed, packages installed by hand are left in the venv
+    let old_config = AppExternal::get_old_config(None)?;
+    let old_lock = Lock::read_installed(None)?;
+    handle_incorrect_config(&mut app, config, old_config, lock, old_lock).await
+}
+
+/// Requirement written to lootbox.toml for a package, checking the index has a version for it
+async fn version_to_add(
+    package: &str,
+    version: Option<String>,
+    index_url: &str,
+    pre: &bool,
+    bound: &Option<Bound>,
+    config: &Config,
+    cache: &Cache,
+) -> Result<String, LootError> {
+    // Versions given explicitly are pinned unless a bound is asked for too
+    match version {
+        Some(version) if Version::from_str(&version).is_ok() => {
+            if version_exists(index_url, package, &version, cache).await? {
+                Ok(bounded_requirement(&version, bound.unwrap_or(Bound::Exact)))
+            } else {
+                Err(LootError::resolution(format!(
+                    "Version {} of {} does not exist",
+                    version, package
+                )))
+            }
+        }
+        Some(_) if bound.is_some() => Err(LootError::config(
+            "--bound only applies to a version, not to a version requirement",
+        )),
+        Some(requirement) => {
+            let specifiers = parse_requirement(&requirement).map_err(|error| {
+                LootError::config(format!("Invalid version requirement: {}", error))
+            })?;
+
+            let versions = get_versions_of_package(index_url, package, cache).await?;
+            let any_match = versions
+                .iter()
+                .filter_map(|version| Version::from_str(version).ok())
+                .any(|version| specifiers.contains(&version));
+            if !any_match {
+                return Err(LootError::resolution(format!(
+                    "No version of {} matches {}",
+                    package, requirement
+                )));
+            }
+
+            Ok(requirement)
+        }
+        // Pre-releases are only picked with --pre, or when they are asked for explicitly above
+        None => {
+            let versions = get_versions_of_package(index_url, package, cache).await?;
+            let versions: Vec<(Version, String)> = versions
+                .into_iter()
+                .filter_map(|version| Some((Version::from_str(&version).ok()?, version)))
+                .collect();
+            if versions.is_empty() {
+                return Err(
+                    LootError::resolution(format!("{} has no versions", package)).with_hint(
+                        "Check the name of the package and the index it is looked up in",
+                    ),
+                );
+            }
+
+            match versions
+                .into_iter()
+                .filter(|(parsed, _)| *pre || !parsed.any_prerelease())
+                .max_by(|a, b| a.0.cmp(&b.0))
+            {
+                Some((_, version)) => {
+                    Ok(bounded_requirement(&version, bound.unwrap_or(config.bound)))
+                }
+                None => Err(
+                    LootError::resolution(format!("{} only has pre-releases", package))
+                        .with_hint("Use --pre or ask for a version explicitly"),
+                ),
+            }
+        }
+    }
 }
@@ -1,73 +1,301 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::process;
-use tokio::{
-    sync::mpsc::{Receiver, Sender},
-    task::JoinHandle,
-};
+use tokio::task::JoinHandle;
 use toml;
 
-use crate::commands::{Command, CommandOutput};
+use crate::cache::Cache;
+use crate::commands::{execute_command, run_child, venv_command, Command, CommandOutput};
+use crate::error::{Context, LootError};
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
 pub struct Config {
     pub name: String,
     pub python_version: String,
-    pub requirements: HashMap<String, String>,
+    pub requirements: HashMap<String, Dependency>,
+    /// Named groups of requirements that are not needed to run the project, like dev or test
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub groups: HashMap<String, HashMap<String, Dependency>>,
+    /// Package indexes besides PyPI
+    #[serde(default, rename = "index", skip_serializing_if = "Vec::is_empty")]
+    pub indexes: Vec<Index>,
+    #[serde(default, skip_serializing_if = "ResolutionStrategy::is_highest")]
+    pub resolution: ResolutionStrategy,
+    /// How `loot add` writes the requirement of the version it picks
+    #[serde(default, skip_serializing_if = "Bound::is_major")]
+    pub bound: Bound,
+}
+
+/// Requirement written by `loot add` for a version, 4.12.3 for example. The exact version is always in the lock
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Bound {
+    /// 4.12.3
+    Exact,
+    /// ~=4.12.3
+    Compatible,
+    /// >=4.12.3,<5, or >=0.27.1,<0.28 before 1.0
+    #[default]
+    Major,
+    /// >=4.12.3
+    Lower,
+}
+
+impl Bound {
+    pub fn is_major(&self) -> bool {
+        self == &Bound::Major
+    }
+}
+
+/// Which of the versions the requirements allow the resolver picks. The lowest ones are useful to test that the
+/// minimum versions in the requirements still work
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResolutionStrategy {
+    #[default]
+    Highest,
+    Lowest,
+    /// Lowest versions for the requirements of the project, highest for their dependencies
+    LowestDirect,
+}
+
+impl ResolutionStrategy {
+    pub fn is_highest(&self) -> bool {
+        self == &ResolutionStrategy::Highest
+    }
+}
+
+/// A package index implementing the Simple API
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct Index {
+    pub name: String,
+    pub url: String,
+    /// Used instead of PyPI for the packages not pinned to an index
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub default: bool,
+}
+
+impl Config {
+    /// Requirements of a group, `None` is the main requirements
+    pub fn group_requirements_mut(
+        &mut self,
+        group: &Option<String>,
+    ) -> &mut HashMap<String, Dependency> {
+        match group {
+            Some(group) => self.groups.entry(group.to_owned()).or_default(),
+            None => &mut self.requirements,
+        }
+    }
+
+    /// Url of the index a package is looked up in. A requirement can pin a named index, the rest use the default one
+    pub fn index_for(&self, package: &str) -> Result<String, LootError> {
+        let package = crate::utils::normalize_name(package);
+        let pinned = std::iter::once(&self.requirements)
+            .chain(self.groups.values())
+            .flatten()
+            .filter(|(name, _)| crate::utils::normalize_name(name) == package)
+            .find_map(|(_, dependency)| dependency.index());
+
+        let index = match pinned {
+            Some(name) => Some(self.get_index(name)?),
+            None => self.indexes.iter().find(|index| index.default),
+        };
+
+        Ok(match index {
+            Some(index) => index.url.trim_end_matches('/').to_owned(),
+            None => crate::versions::PYPI_INDEX.to_owned(),
+        })
+    }
+
+    pub fn get_index(&self, name: &str) -> Result<&Index, LootError> {
+        self.indexes
+            .iter()
+            .find(|index| index.name == name)
+            .context(|| {
+                LootError::config(format!(
+                    "Index {} is not declared in {}",
+                    name,
+                    crate::DEPENDENCIES_FILE
+                ))
+                .with_hint(format!(
+                    "Add it as an [[index]] with a name and url to {}",
+                    crate::DEPENDENCIES_FILE
+                ))
+            })
+    }
+}
+
+/// A requirement in lootbox.toml. Either a version requirement or a table with extras and markers
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[serde(untagged)]
+pub enum Dependency {
+    Version(String),
+    Detailed(DetailedDependency),
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Default)]
+pub struct DetailedDependency {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extras: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub markers: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+    /// Branch, tag or commit of the git repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub editable: bool,
+    /// Direct link to a wheel or source distribution
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Name of the index the package has to come from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+}
+
+impl Dependency {
+    pub fn version(&self) -> &str {
+        match self {
+            Dependency::Version(version) => version,
+            Dependency::Detailed(dependency) => dependency.version.as_deref().unwrap_or("*"),
+        }
+    }
+
+    pub fn extras(&self) -> &[String] {
+        match self {
+            Dependency::Version(_) => &[],
+            Dependency::Detailed(dependency) => &dependency.extras,
+        }
+    }
+
+    pub fn markers(&self) -> Option<&str> {
+        match self {
+            Dependency::Version(_) => None,
+            Dependency::Detailed(dependency) => dependency.markers.as_deref(),
+        }
+    }
+
+    pub fn index(&self) -> Option<&str> {
+        match self {
+            Dependency::Version(_) => None,
+            Dependency::Detailed(dependency) => dependency.index.as_deref(),
+        }
+    }
+
+    /// Git, path and url dependencies are not looked up in an index
+    pub fn direct_source(&self) -> Option<&DetailedDependency> {
+        match self {
+            Dependency::Detailed(dependency)
+                if dependency.git.is_some()
+                    || dependency.path.is_some()
+                    || dependency.url.is_some() =>
+            {
+                Some(dependency)
+            }
+            _ => None,
+        }
+    }
+
+    /// Same dependency with another version requirement
+    pub fn with_version(&self, version: String) -> Dependency {
+        match self {
+            Dependency::Version(_) => Dependency::Version(version),
+            Dependency::Detailed(dependency) => Dependency::Detailed(DetailedDependency {
+                version: Some(version),
+                ..dependency.clone()
+            }),
+        }
+    }
+
+    /// Same dependency installed with other extras
+    pub fn with_extras(&self, extras: Vec<String>) -> Dependency {
+        Dependency::Detailed(DetailedDependency {
+            extras,
+            ..self.to_detailed()
+        })
+    }
+
+    /// Same dependency only installed where the markers apply
+    pub fn with_markers(&self, markers: String) -> Dependency {
+        Dependency::Detailed(DetailedDependency {
+            markers: Some(markers),
+            ..self.to_detailed()
+        })
+    }
+
+    fn to_detailed(&self) -> DetailedDependency {
+        match self {
+            Dependency::Version(version) => DetailedDependency {
+                version: Some(version.clone()),
+                ..Default::default()
+            },
+            Dependency::Detailed(dependency) => dependency.clone(),
+        }
+    }
+
+    /// Same dependency pinned to a named index
+    pub fn with_index(&self, index: String) -> Dependency {
+        match self {
+            Dependency::Version(version) => Dependency::Detailed(DetailedDependency {
+                version: Some(version.clone()),
+                index: Some(index),
+                ..Default::default()
+            }),
+            Dependency::Detailed(dependency) => Dependency::Detailed(DetailedDependency {
+                index: Some(index),
+                ..dependency.clone()
+            }),
+        }
+    }
+}
+
+/// How errors that tools may want to read, like resolution conflicts, are printed
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    #[default]
+    Text,
+    Json,
 }
 
 pub struct AppExternal<'a> {
     pub data_path: &'a std::path::Path,
     pub app_config: Option<Config>,
+    pub cache: Cache,
 
-    is_internal: bool,
-    sender: Sender<Command>,
-    receiver: Receiver<CommandOutput>,
+    /// Venv the internal commands run in, set by `make_internal`
+    venv: Option<PathBuf>,
 }
 
 impl<'a> AppExternal<'a> {
-    pub fn new(
-        data_path: &'a std::path::Path,
-        sender: Sender<Command>,
-        receiver: Receiver<CommandOutput>,
-    ) -> Self {
+    pub fn new(data_path: &'a std::path::Path, offline: bool) -> Self {
         Self {
             data_path,
             app_config: None,
-            is_internal: false,
-            sender,
-            receiver,
+            cache: Cache::new(data_path, offline),
+            venv: None,
         }
     }
 
-    pub async fn run_external_command(&self, command: String) {
-        self.sender
-            .send(Command::ExternalCommand(command))
-            .await
-            .expect(
-                "Command receiver droped, this should NEVER happen, the commands thread crashed.",
-            );
+    pub async fn run_external_command(&self, args: Vec<String>) -> CommandOutput {
+        execute_command(Command::ExternalCommand(args)).await
     }
 
-    pub async fn run_external_command_from_dir(&self, command: String, dir: PathBuf) {
-        self.sender
-            .send(Command::ExternalCommandFromDirectory(command, dir))
-            .await
-            .expect(
-                "Command receiver droped, this should NEVER happen, the commands thread crashed.",
-            );
+    pub async fn run_external_command_from_dir(
+        &self,
+        args: Vec<String>,
+        dir: PathBuf,
+    ) -> CommandOutput {
+        execute_command(Command::ExternalCommandFromDirectory(args, dir)).await
     }
 
-    pub async fn run_external_command_with_output(&mut self, command: String) -> CommandOutput {
-        self.sender
-            .send(Command::ExternalCommandWithOutput(command))
-            .await
-            .expect(
-                "Command receiver droped, this should NEVER happen, the commands thread crashed.",
-            );
-
-        self.receiver.recv().await.expect("Command output receiver droped, this should NEVER happen, the commands thread crashed.")
+    pub async fn run_external_command_with_output(&self, args: Vec<String>) -> CommandOutput {
+        execute_command(Command::ExternalCommandWithOutput(args)).await
     }
 
     #[cfg(target_os = "windows")]
@@ -103,7 +331,8 @@ impl<'a> AppExternal<'a> {
         }
     }
 
-    pub fn get_old_config(path: Option<std::path::PathBuf>) -> Config {
+    /// Config the venv was last synced with
+    pub fn get_old_config(path: Option<std::path::PathBuf>) -> Result<Config, LootError> {
         let location = match path {
             Some(path) => path,
             None => std::path::PathBuf::new(),
@@ -111,148 +340,122 @@ impl<'a> AppExternal<'a> {
 
         let path_to_file = location.join(".lootbox").join(crate::DEPENDENCIES_FILE);
 
-        let old_config = std::fs::read_to_string(path_to_file).expect("Error reading old config");
+        let error = || {
+            LootError::venv(format!(
+                "Could not read .lootbox/{}",
+                crate::DEPENDENCIES_FILE
+            ))
+            .with_hint("Delete .lootbox and run loot sync to make the venv again")
+        };
+        let old_config = std::fs::read_to_string(path_to_file).context(error)?;
 
-        toml::from_str(&old_config).expect("Error parsing old config")
+        toml::from_str(&old_config).context(error)
     }
 
-    // This function does not check wether in valid context
-    pub async fn get_access_venv_command(&self, path: Option<std::path::PathBuf>) -> String {
+    pub fn save_old_config(
+        path: Option<std::path::PathBuf>,
+        config: &Config,
+    ) -> Result<(), LootError> {
         let location = match path {
             Some(path) => path,
             None => std::path::PathBuf::new(),
         };
 
-        #[cfg(target_os = "windows")]
-        let location = location
-            .join(".lootbox")
-            .join("venv")
-            .join("Scripts")
-            .join("Activate.ps1");
+        crate::utils::create_file_with_content(
+            &location.join(".lootbox").join(crate::DEPENDENCIES_FILE),
+            toml::to_string(config)
+                .expect("Error serializing new config")
+                .as_bytes(),
+        )
+        .context(|| {
+            LootError::venv(format!(
+                "Error writing .lootbox/{}",
+                crate::DEPENDENCIES_FILE
+            ))
+        })
+    }
 
-        #[cfg(not(target_os = "windows"))]
-        let location = location
+    /// Venv of the project at `path`, the current directory by default
+    pub fn venv_path(path: Option<std::path::PathBuf>) -> PathBuf {
+        std::env::current_dir()
+            .expect("Error reading current dir")
+            .join(path.unwrap_or_default())
             .join(".lootbox")
             .join("venv")
-            .join("bin")
-            .join("activate");
-
-        #[cfg(target_os = "windows")]
-        return location.to_string_lossy().to_string();
-
-        #[cfg(not(target_os = "windows"))]
-        return format!(". {}", location.to_string_lossy().to_string());
     }
 
+    /// Runs commands in the venv one after the other on their own task, stopping at the first one that fails. The
+    /// handle tells wether they all succeeded
     pub async fn run_paralel_internal_command(
         &self,
         path: Option<std::path::PathBuf>,
-        command: String,
-    ) -> JoinHandle<()> {
-        #[cfg(target_os = "windows")]
-        let command_to_run = format!(
-            "{}; {}",
-            self.get_access_venv_command(path).await.clone(),
-            command
-        );
-
-        #[cfg(not(target_os = "windows"))]
-        let command_to_run = format!(
-            "{} && {}",
-            self.get_access_venv_command(path).await.clone(),
-            command
-        );
+        commands: Vec<Vec<String>>,
+    ) -> JoinHandle<bool> {
+        let venv = AppExternal::venv_path(path);
 
         tokio::spawn(async move {
-            #[cfg(target_os = "windows")]
-            let _ = process::Command::new("powershell")
-                .args(&["-Command", &command_to_run])
-                .stdout(process::Stdio::inherit())
-                .stderr(process::Stdio::inherit())
-                .output()
-                .expect("Error running command");
-
-            #[cfg(not(target_os = "windows"))]
-            let _ = process::Command::new("sh")
-                .args(&["-c", &command_to_run])
-                .stdout(process::Stdio::inherit())
-                .stderr(process::Stdio::inherit())
-                .output()
-                .expect("Error running command");
+            for args in commands {
+                if !run_child(venv_command(&args, &venv), false)
+                    .await
+                    .status
+                    .success()
+                {
+                    return false;
+                }
+            }
+            true
         })
     }
 
-    pub async fn make_internal(&mut self, path: Option<std::path::PathBuf>) {
+    /// Reads lootbox.toml and prepares the venv the internal commands run in, creating it if it does not exist
+    pub async fn make_internal(
+        &mut self,
+        path: Option<std::path::PathBuf>,
+    ) -> Result<(), LootError> {
         let location = match path {
             Some(path) => path,
             None => std::path::PathBuf::new(),
         };
 
-        self.app_config = toml::from_str(
-            &std::fs::read_to_string(location.join(crate::DEPENDENCIES_FILE))
-                .expect("No config file. Might not be inside a lootbox context"),
-        )
-        .expect("Error parsing config");
-
-        // Validation
-        if !location.join(crate::DEPENDENCIES_FILE).exists() {
-            panic!("Not inside a project");
-        } else if !location.join(".lootbox").exists() {
-            // Se supone que si existe es valido
+        let config =
+            std::fs::read_to_string(location.join(crate::DEPENDENCIES_FILE)).context(|| {
+                LootError::config(format!("There is no {} here", crate::DEPENDENCIES_FILE))
+                    .with_hint(
+                    "Run it inside a project, or create one with loot new {name} {python_version}",
+                )
+            })?;
+        let config: Config = toml::from_str(&config)
+            .context(|| LootError::config(format!("{} is not valid", crate::DEPENDENCIES_FILE)))?;
+        let python_version = config.python_version.clone();
+        self.app_config = Some(config);
+
+        // Se supone que si existe es valido
+        if !location.join(".lootbox").exists() {
             Box::pin(crate::new::create_lootbox_dir(
                 Some(&location),
-                &self
-                    .app_config
-                    .clone()
-                    .expect("Config file does not exist")
-                    .python_version,
+                &python_version,
                 self,
             ))
-            .await;
+            .await?;
         }
 
-        self.sender
-            .send(Command::InternalCommand(
-                self.get_access_venv_command(Some(location)).await,
-            ))
-            .await
-            .expect(
-                "Command receiver droped, this should NEVER happen, the commands thread crashed.",
-            );
-
-        self.is_internal = true;
+        self.venv = Some(AppExternal::venv_path(Some(location)));
+        Ok(())
     }
 
-    pub async fn run_internal_command(&self, command: String) -> Option<()> {
-        if !self.is_internal {
-            return None;
-        }
+    /// Runs a program of the venv with its arguments as they are, without going through a shell
+    pub async fn run_internal_command(&self, args: Vec<String>) -> Option<CommandOutput> {
+        let venv = self.venv.clone()?;
 
-        self.sender
-            .send(Command::InternalCommand(command))
-            .await
-            .expect(
-                "Command receiver droped, this should NEVER happen, the commands thread crashed.",
-            );
-
-        Some(())
+        Some(execute_command(Command::InternalCommand(args, venv)).await)
     }
 
     pub async fn run_internal_command_with_output(
-        &mut self,
-        command: String,
+        &self,
+        args: Vec<String>,
     ) -> Option<CommandOutput> {
-        if !self.is_internal {
-            return None;
-        }
-
-        self.sender
-            .send(Command::InternalCommandWithOutput(command))
-            .await
-            .expect(
-                "Command receiver droped, this should NEVER happen, the commands thread crashed.",
-            );
+        let venv = self.venv.clone()?;
 
-        Some(self.receiver.recv().await.expect("Command output receiver droped, this should NEVER happen, the commands thread crashed."))
+        Some(execute_command(Command::InternalCommandWithOutput(args, venv)).await)
     }
 }
@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+
+use crate::app::AppExternal;
+use crate::error::LootError;
+use crate::export::requirement_line;
+use crate::lock::Lock;
+use crate::utils::{clone_dir, create_file_with_content};
+
+pub async fn bundle_project(mut app: AppExternal<'_>) -> Result<(), LootError> {
+    app.make_internal(None).await?;
+    let config = app.app_config.clone().expect("Config file not found");
+
+    let lock = Lock::get_or_resolve(None, &config, &app).await?;
+
+    let _ = std::fs::remove_dir_all(PathBuf::from("./target"));
+    clone_dir(&PathBuf::from("./src"), &PathBuf::from("./target"))
+        .expect("Error cloning souce code");
+
+    // Groups are only needed while developing, the bundle gets the main requirements
+    let packages = lock.packages_for_groups(&[])?;
+    let mut requirements = String::new();
+    for index in &lock.indexes {
+        let url = index.url.trim_end_matches('/');
+        if packages.iter().any(|package| package.source == url) {
+            requirements.push_str(&format!("--extra-index-url {}\n", url));
+        }
+    }
+    // Git, path and url packages are not in any index
+    for package in packages {
+        requirements.push_str(&requirement_line(package));
+        requirements.push('\n');
+    }
+    create_file_with_content(
+        &PathBuf::from("./target/requirements.txt"),
+        requirements.as_bytes(),
+    )
+    .expect("Error writing requirements file");
+    Ok(())
+}
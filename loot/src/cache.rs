@@ -0,0 +1,182 @@
+use pep440_rs::Version;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::error::{Context, LootError};
+use crate::utils::{create_file_with_content, normalize_name, short_hash};
+
+const CACHE_DIRECTORY: &str = "cache";
+const HTTP_DIRECTORY: &str = "http";
+const WHEELS_DIRECTORY: &str = "wheels";
+const HEADERS_FILE: &str = "headers.json";
+
+/// Cache shared by every project, with the responses of indexes, downloads and the wheels installed in venvs
+#[derive(Debug, Clone)]
+pub struct Cache {
+    pub path: PathBuf,
+    /// Nothing is fetched from the network, everything has to be in the cache already
+    pub offline: bool,
+}
+
+/// What is kept of a response besides its body
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct CachedHeaders {
+    etag: Option<String>,
+    content_type: String,
+}
+
+impl Cache {
+    pub fn new(data_path: &Path, offline: bool) -> Cache {
+        Cache {
+            path: data_path.join(CACHE_DIRECTORY),
+            offline,
+        }
+    }
+
+    /// Reads a url. Returns the content type and the body, or `None` if it does not exist
+    pub async fn fetch(
+        &self,
+        url: &str,
+        accept: &str,
+    ) -> Result<Option<(String, Vec<u8>)>, LootError> {
+        let Some((content_type, file)) = self.fetch_file(url, accept).await? else {
+            return Ok(None);
+        };
+        let content = fs::read(&file)
+            .context(|| LootError::network(format!("Error reading {}", file.display())))?;
+
+        Ok(Some((content_type, content)))
+    }
+
+    /// Same as `fetch` but returns where the body is. `file://` urls are read in place, the rest are cached and
+    /// revalidated with their ETag
+    pub async fn fetch_file(
+        &self,
+        url: &str,
+        accept: &str,
+    ) -> Result<Option<(String, PathBuf)>, LootError> {
+        let parsed =
+            Url::parse(url).context(|| LootError::config(format!("Invalid url {}", url)))?;
+
+        if parsed.scheme() == "file" {
+            let path = parsed
+                .to_file_path()
+                .map_err(|()| LootError::config(format!("Invalid file url {}", url)))?;
+            let content_type = match path.extension().and_then(|extension| extension.to_str()) {
+                Some("json") => "application/vnd.pypi.simple.v1+json",
+                Some("html") => "text/html",
+                _ => "application/octet-stream",
+            };
+
+            return Ok(path.is_file().then(|| (content_type.to_owned(), path)));
+        }
+
+        let entry = self.path.join(HTTP_DIRECTORY).join(short_hash(url));
+        let file_name = parsed
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .filter(|file_name| !file_name.is_empty())
+            .unwrap_or("response")
+            .to_owned();
+        let file = entry.join(&file_name);
+        let cached: Option<CachedHeaders> = fs::read_to_string(entry.join(HEADERS_FILE))
+            .ok()
+            .and_then(|headers| serde_json::from_str(&headers).ok())
+            .filter(|_| file.is_file());
+
+        if self.offline {
+            let cached = cached.ok_or_else(|| missing_offline(url))?;
+            return Ok(Some((cached.content_type, file)));
+        }
+
+        let mut request = reqwest::Client::new()
+            .get(parsed)
+            .header(reqwest::header::ACCEPT, accept);
+        if let Some(etag) = cached.as_ref().and_then(|cached| cached.etag.as_ref()) {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        let response = request.send().await.context(|| fetch_error(url))?;
+
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            if let Some(cached) = cached {
+                return Ok(Some((cached.content_type, file)));
+            }
+        }
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response.error_for_status().context(|| fetch_error(url))?;
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+                .map(|value| value.to_owned())
+        };
+        let headers = CachedHeaders {
+            etag: header(reqwest::header::ETAG),
+            content_type: header(reqwest::header::CONTENT_TYPE)
+                .unwrap_or_else(|| "text/html".to_owned()),
+        };
+        let content = response.bytes().await.context(|| fetch_error(url))?;
+
+        // The headers are written last, an entry without them is incomplete
+        let write_error = || {
+            LootError::config(format!(
+                "Error writing to the cache in {}",
+                self.path.display()
+            ))
+            .with_hint("Check the permissions of the data dir")
+        };
+        fs::create_dir_all(&entry).context(write_error)?;
+        let _ = fs::remove_file(entry.join(HEADERS_FILE));
+        create_file_with_content(&file, &content).context(write_error)?;
+        create_file_with_content(
+            &entry.join(HEADERS_FILE),
+            serde_json::to_string(&headers)
+                .expect("Error serializing cache entry")
+                .as_bytes(),
+        )
+        .context(write_error)?;
+
+        Ok(Some((headers.content_type, file)))
+    }
+
+    /// Wheels of the packages of an index that have been installed in any venv
+    pub fn wheels_dir(&self, index: &str) -> PathBuf {
+        self.path.join(WHEELS_DIRECTORY).join(short_hash(index))
+    }
+
+    pub fn has_wheel(&self, index: &str, package: &str, version: &str) -> bool {
+        let Ok(entries) = fs::read_dir(self.wheels_dir(index)) else {
+            return false;
+        };
+        let package = normalize_name(package);
+        let version = Version::from_str(version).ok();
+
+        entries.filter_map(|entry| entry.ok()).any(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(wheel) = file_name.strip_suffix(".whl") else {
+                return false;
+            };
+            let mut parts = wheel.split('-');
+
+            parts.next().map(normalize_name) == Some(package.clone())
+                && parts.next().and_then(|other| Version::from_str(other).ok()) == version
+        })
+    }
+}
+
+fn fetch_error(url: &str) -> LootError {
+    LootError::network(format!("Error fetching {}", url))
+        .with_hint("Check the connection and the index urls in lootbox.toml")
+}
+
+pub fn missing_offline(what: &str) -> LootError {
+    LootError::network(format!("{} is not in the cache", what))
+        .with_hint("Run without --offline to download it")
+}
@@ -1,195 +1,175 @@
-use std::io::{BufRead, BufReader, Read, Write};
-use std::ops::DerefMut;
-use std::path::PathBuf;
-use std::process;
-use std::sync::{Arc, Mutex};
-use tokio::sync::mpsc::Sender;
+use inline_colorization::*;
+use std::ffi::OsString;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output, Stdio};
+use tokio::process;
 
+use crate::shutdown::{is_stopping, spawn_child};
+
+/// Programs and their arguments, run without a shell so every argument reaches them exactly as given
 #[derive(Debug)]
 pub enum Command {
-    InternalCommand(String),
-    InternalCommandWithOutput(String),
-    ExternalCommand(String),
-    ExternalCommandWithOutput(String),
-    ExternalCommandFromDirectory(String, PathBuf),
+    /// Run with the environment of a venv, the `PathBuf`. The program is looked up in the venv first
+    InternalCommand(Vec<String>, PathBuf),
+    InternalCommandWithOutput(Vec<String>, PathBuf),
+    ExternalCommand(Vec<String>),
+    ExternalCommandWithOutput(Vec<String>),
+    ExternalCommandFromDirectory(Vec<String>, PathBuf),
 }
 
 #[derive(Debug)]
-#[allow(unused)]
 pub struct CommandOutput {
+    pub status: ExitStatus,
+    /// Only captured by the commands `WithOutput`, the rest print to the terminal
     pub stdout: String,
     pub stderr: String,
 }
 
-pub async fn execute_command(
-    cmd: Command,
-    stdout: Arc<Mutex<std::process::ChildStdout>>,
-    stderr: Arc<Mutex<std::process::ChildStderr>>,
-    stdin: Arc<tokio::sync::Mutex<std::process::ChildStdin>>,
-    sender: &Sender<CommandOutput>,
-) {
-    println!("{:?}", cmd);
-    match cmd {
-        Command::ExternalCommand(cmd) => {
-            let command_parts: Vec<&str> = cmd.split_whitespace().collect();
-
-            let _ = process::Command::new(command_parts[0])
-                .args(&command_parts[1..])
-                .stdout(process::Stdio::inherit())
-                .stderr(process::Stdio::inherit())
-                .output()
-                .expect("Error running external command");
-        }
-        Command::ExternalCommandWithOutput(cmd) => {
-            let command_parts: Vec<&str> = cmd.split_whitespace().collect();
-
-            let result = process::Command::new(command_parts[0])
-                .args(&command_parts[1..])
-                .output()
-                .expect("Error running external command");
-
-            sender
-                .send(CommandOutput {
-                    stdout: String::from_utf8_lossy(&result.stdout).to_string(),
-                    stderr: String::from_utf8_lossy(&result.stderr).to_string(),
-                })
-                .await
-                .expect("channel was closed. WTF");
-        }
-        Command::ExternalCommandFromDirectory(cmd, dir) => {
-            let command_parts: Vec<&str> = cmd.split_whitespace().collect();
-
-            let _ = process::Command::new(command_parts[0])
-                .current_dir(dir)
-                .args(&command_parts[1..])
-                .stdout(process::Stdio::inherit())
-                .stderr(process::Stdio::inherit())
-                .output()
-                .expect("Error running external command");
-        }
-        Command::InternalCommand(cmd) => {
-            let mut stdin = stdin.lock().await;
-
-            #[cfg(target_os = "windows")]
-            writeln!(
-                stdin,
-                "{}; echo finalizau; $host.ui.WriteErrorLine('finalizau')",
-                cmd
-            )
-            .expect("Error writing to stdin");
-
-            #[cfg(not(target_os = "windows"))]
-            writeln!(stdin, "{}; echo finalizau; echo finalizau 1>&2", cmd)
-                .expect("Error writing to stdin");
-
-            drop(stdin);
-
-            let stdout_handle = tokio::spawn(async move {
-                let mut stdout = stdout.lock().expect("Lock got poisoned");
-
-                let mut buffer = [0; 1024];
-                loop {
-                    let bytes_read = stdout.read(&mut buffer).expect("Error reading stdout");
-
-                    // Sino da error por overflow si el input es menor que finalizau
-                    if bytes_read >= b"finalizau".len() {
-                        if let Some(finalizau_location) =
-                            bytes_contains_subytes(&buffer[..bytes_read], b"finalizau")
-                        {
-                            print!("{}", String::from_utf8_lossy(&buffer[..finalizau_location]));
-                            break;
-                        }
-                    }
-
-                    print!("{}", String::from_utf8_lossy(&buffer[..bytes_read]));
-                }
-            });
-
-            let stderr_handle = tokio::spawn(async move {
-                let mut stderr = stderr.lock().expect("Lock got poisoned");
-                let stderr_wrapper = BufReader::new(stderr.deref_mut());
-
-                for line in stderr_wrapper.lines() {
-                    let line = line.unwrap_or_default();
-
-                    if line == "finalizau" {
-                        break;
-                    }
-
-                    println!("{}", line);
-                }
-            });
-
-            let _ = tokio::join!(stdout_handle, stderr_handle);
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+
+    /// Exit code to pass on, 128 + the signal if the command was killed by one like shells do
+    pub fn exit_code(&self) -> i32 {
+        #[cfg(not(target_os = "windows"))]
+        if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&self.status) {
+            return 128 + signal;
         }
-        Command::InternalCommandWithOutput(cmd) => {
-            let mut stdin = stdin.lock().await;
 
-            #[cfg(target_os = "windows")]
-            writeln!(
-                stdin,
-                "{}; echo finalizau; $host.ui.WriteErrorLine('finalizau')",
-                cmd
-            )
-            .expect("Error writing to stdin");
+        self.status.code().unwrap_or(1)
+    }
+}
 
-            #[cfg(not(target_os = "windows"))]
-            writeln!(stdin, "{}; echo finalizau; echo finalizau 1>&2", cmd)
-                .expect("Error writing to stdin");
+/// Runs a command until it exits. Commands that are not captured share the terminal, stdin included
+pub async fn execute_command(cmd: Command) -> CommandOutput {
+    let (command, capture) = match cmd {
+        Command::InternalCommand(args, venv) => (venv_command(&args, &venv), false),
+        Command::InternalCommandWithOutput(args, venv) => (venv_command(&args, &venv), true),
+        Command::ExternalCommand(args) => (program_command(&args), false),
+        Command::ExternalCommandWithOutput(args) => (program_command(&args), true),
+        Command::ExternalCommandFromDirectory(args, dir) => {
+            let mut command = program_command(&args);
+            command.current_dir(dir);
+            (command, false)
+        }
+    };
 
-            drop(stdin);
+    let output = run_child(command, capture).await;
+    CommandOutput {
+        status: output.status,
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+    }
+}
 
-            let stdout_handle = tokio::spawn(async move {
-                let mut stdout_string = String::new();
+/// Runs a process until it exits, letting `shutdown` stop it if lootbox gets a signal. Once lootbox is stopping, no
+/// process starts and none of their results are returned, the signal handler exits instead
+pub async fn run_child(mut command: process::Command, capture: bool) -> Output {
+    if capture {
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+    }
 
-                let mut stdout = stdout.lock().expect("Lock got poisoned");
-                let stdout_wrapper = BufReader::new(stdout.deref_mut());
+    // Programs that may read the terminal stay in its foreground process group, so they get Ctrl-C from it. The
+    // rest get their own group so a signal reaches whatever they start, like the compilers run by make
+    let own_group = capture || !std::io::stdin().is_terminal();
+    #[cfg(not(target_os = "windows"))]
+    if own_group {
+        command.process_group(0);
+    }
 
-                for line in stdout_wrapper.lines() {
-                    let line = line.unwrap_or_default();
+    let (child, guard) = match spawn_child(&mut command, own_group) {
+        Some(Ok(started)) => started,
+        Some(Err(error)) => return not_started(&command, &error, capture),
+        None => return std::future::pending().await,
+    };
+    let output = child
+        .wait_with_output()
+        .await
+        .expect("Error waiting for command");
+    drop(guard);
+
+    if is_stopping() {
+        return std::future::pending().await;
+    }
+    output
+}
 
-                    if line == "finalizau" {
-                        break;
-                    }
+/// What a shell gives for a program it can not start, exit code 127 if it does not exist and 126 otherwise. The
+/// error goes where the stderr of the program would
+fn not_started(command: &process::Command, error: &std::io::Error, capture: bool) -> Output {
+    let code = if error.kind() == std::io::ErrorKind::NotFound {
+        127
+    } else {
+        126
+    };
+    let message = format!(
+        "Could not run {}: {}",
+        command.as_std().get_program().to_string_lossy(),
+        error
+    );
+
+    let stderr = if capture {
+        message.into_bytes()
+    } else {
+        eprintln!("{color_red}error: {}{color_reset}", message);
+        Vec::new()
+    };
+    Output {
+        status: exit_status(code),
+        stdout: Vec::new(),
+        stderr,
+    }
+}
 
-                    stdout_string += &(line + "\n");
-                }
-                stdout_string
-            });
+#[cfg(not(target_os = "windows"))]
+fn exit_status(code: i32) -> ExitStatus {
+    std::os::unix::process::ExitStatusExt::from_raw(code << 8)
+}
 
-            let stderr_handle = tokio::spawn(async move {
-                let mut stderr_string = String::new();
+#[cfg(target_os = "windows")]
+fn exit_status(code: i32) -> ExitStatus {
+    std::os::windows::process::ExitStatusExt::from_raw(code as u32)
+}
 
-                let mut stderr = stderr.lock().expect("Lock got poisoned");
-                let stderr_wrapper = BufReader::new(stderr.deref_mut());
+/// A program and its arguments
+fn program_command(args: &[String]) -> process::Command {
+    let (program, args) = args.split_first().expect("No program to run");
 
-                for line in stderr_wrapper.lines() {
-                    let line = line.unwrap_or_default();
+    let mut command = process::Command::new(program);
+    command.args(args);
+    command
+}
 
-                    if line == "finalizau" {
-                        break;
-                    }
+/// A program and its arguments with the environment of a venv, the way activating it would set it. The PATH of the
+/// venv is also where the program is looked up
+pub fn venv_command(args: &[String], venv: &Path) -> process::Command {
+    let mut command = program_command(args);
+    command
+        .envs(venv_environment(venv))
+        .env_remove("PYTHONHOME");
+    command
+}
 
-                    stderr_string += &(line + "\n");
-                }
-                stderr_string
-            });
+/// Arguments that are all known strings, like `args(&["pip", "install"])`
+pub fn args(parts: &[&str]) -> Vec<String> {
+    parts.iter().map(|part| part.to_string()).collect()
+}
 
-            let (stdout_string, stderr_string) = tokio::join!(stdout_handle, stderr_handle);
+/// `VIRTUAL_ENV` and a `PATH` that starts with the scripts of the venv
+pub fn venv_environment(venv: &Path) -> Vec<(&'static str, OsString)> {
+    #[cfg(target_os = "windows")]
+    let scripts = venv.join("Scripts");
+    #[cfg(not(target_os = "windows"))]
+    let scripts = venv.join("bin");
 
-            sender
-                .send(CommandOutput {
-                    stdout: stdout_string.expect("Error handling stdout"),
-                    stderr: stderr_string.expect("Error handling stderr"),
-                })
-                .await
-                .expect("channel was closed. WTF");
-        }
-    }
-}
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let path = std::env::join_paths(std::iter::once(scripts).chain(std::env::split_paths(&path)))
+        .expect("Invalid PATH");
 
-fn bytes_contains_subytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
-    (0..haystack.len() - needle.len() + 1)
-        .filter(|&i| haystack[i..i + needle.len()] == needle[..])
-        .next()
+    vec![("VIRTUAL_ENV", venv.as_os_str().to_owned()), ("PATH", path)]
 }
@@ -0,0 +1,198 @@
+use inline_colorization::*;
+use std::error::Error;
+use std::fmt;
+
+use crate::app::ErrorFormat;
+
+/// Exit code of checks that did not pass, like `sync --check`. They are not errors, what failed is already printed
+pub const CHECK_FAILED: i32 = 1;
+
+/// A failure lootbox explains to the user instead of panicking. Each kind exits with its own code
+#[derive(Debug)]
+pub enum LootError {
+    /// lootbox.toml or another project file is missing or invalid
+    Config(Failure),
+    /// An index, python.org or another server could not be reached
+    Network(Failure),
+    /// The requirements can not be satisfied together
+    Resolution(Failure),
+    /// The python version is not installed or could not be built
+    Interpreter(Failure),
+    /// The venv in .lootbox could not be created or used
+    Venv(Failure),
+}
+
+#[derive(Debug, Default)]
+pub struct Failure {
+    pub message: String,
+    /// What the user can do about it
+    pub hint: Option<String>,
+    pub cause: Option<Box<dyn Error + Send + Sync>>,
+    /// Anything else tools reading `--error-format json` may want, like the conflicts of a resolution
+    pub details: Option<serde_json::Value>,
+}
+
+impl LootError {
+    pub fn config(message: impl fmt::Display) -> LootError {
+        LootError::Config(Failure::new(message))
+    }
+
+    pub fn network(message: impl fmt::Display) -> LootError {
+        LootError::Network(Failure::new(message))
+    }
+
+    pub fn resolution(message: impl fmt::Display) -> LootError {
+        LootError::Resolution(Failure::new(message))
+    }
+
+    pub fn interpreter(message: impl fmt::Display) -> LootError {
+        LootError::Interpreter(Failure::new(message))
+    }
+
+    pub fn venv(message: impl fmt::Display) -> LootError {
+        LootError::Venv(Failure::new(message))
+    }
+
+    pub fn with_hint(mut self, hint: impl fmt::Display) -> LootError {
+        self.failure_mut().hint = Some(hint.to_string());
+        self
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> LootError {
+        self.failure_mut().details = Some(details);
+        self
+    }
+
+    pub fn caused_by(mut self, cause: impl Into<Box<dyn Error + Send + Sync>>) -> LootError {
+        self.failure_mut().cause = Some(cause.into());
+        self
+    }
+
+    /// 1 is left for other failures and checks like `sync --check`, 2 is what clap exits with for invalid arguments
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LootError::Config(_) => 3,
+            LootError::Network(_) => 4,
+            LootError::Resolution(_) => 5,
+            LootError::Interpreter(_) => 6,
+            LootError::Venv(_) => 7,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LootError::Config(_) => "config",
+            LootError::Network(_) => "network",
+            LootError::Resolution(_) => "resolution",
+            LootError::Interpreter(_) => "interpreter",
+            LootError::Venv(_) => "venv",
+        }
+    }
+
+    pub fn failure(&self) -> &Failure {
+        match self {
+            LootError::Config(failure)
+            | LootError::Network(failure)
+            | LootError::Resolution(failure)
+            | LootError::Interpreter(failure)
+            | LootError::Venv(failure) => failure,
+        }
+    }
+
+    fn failure_mut(&mut self) -> &mut Failure {
+        match self {
+            LootError::Config(failure)
+            | LootError::Network(failure)
+            | LootError::Resolution(failure)
+            | LootError::Interpreter(failure)
+            | LootError::Venv(failure) => failure,
+        }
+    }
+
+    /// Messages of the causes, from the closest one
+    fn causes(&self) -> Vec<String> {
+        let mut causes = Vec::new();
+        let mut cause = self.source();
+        while let Some(error) = cause {
+            causes.push(error.to_string());
+            cause = error.source();
+        }
+        causes
+    }
+
+    /// The error as shown in the terminal. The causes are only shown with `--debug`
+    pub fn render(&self, format: ErrorFormat, debug: bool) -> String {
+        let failure = self.failure();
+
+        if format == ErrorFormat::Json {
+            let mut json = serde_json::json!({
+                "kind": self.kind(),
+                "message": failure.message,
+                "hint": failure.hint,
+                "causes": self.causes(),
+            });
+            if let Some(serde_json::Value::Object(details)) = &failure.details {
+                json.as_object_mut()
+                    .expect("Errors are objects")
+                    .extend(details.clone());
+            }
+            return serde_json::to_string_pretty(&json).expect("Error serializing error");
+        }
+
+        let mut rendered = format!("{color_red}error: {}{color_reset}", failure.message);
+        let causes = self.causes();
+        if debug {
+            for cause in &causes {
+                rendered.push_str(&format!("\n  caused by: {}", cause));
+            }
+        }
+        if let Some(hint) = &failure.hint {
+            rendered.push_str(&format!("\n{color_yellow}hint: {}{color_reset}", hint));
+        }
+        if !debug && !causes.is_empty() {
+            rendered.push_str("\nRun with --debug to see what caused it");
+        }
+        rendered
+    }
+}
+
+impl Failure {
+    fn new(message: impl fmt::Display) -> Failure {
+        Failure {
+            message: message.to_string(),
+            ..Failure::default()
+        }
+    }
+}
+
+impl fmt::Display for LootError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.failure().message)
+    }
+}
+
+impl Error for LootError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.failure()
+            .cause
+            .as_deref()
+            .map(|cause| cause as &(dyn Error + 'static))
+    }
+}
+
+/// Turns any error, or a missing value, into a `LootError` that keeps the original error as its cause
+pub trait Context<T> {
+    fn context(self, error: impl FnOnce() -> LootError) -> Result<T, LootError>;
+}
+
+impl<T, E: Error + Send + Sync + 'static> Context<T> for Result<T, E> {
+    fn context(self, error: impl FnOnce() -> LootError) -> Result<T, LootError> {
+        self.map_err(|cause| error().caused_by(cause))
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context(self, error: impl FnOnce() -> LootError) -> Result<T, LootError> {
+        self.ok_or_else(error)
+    }
+}
@@ -0,0 +1,495 @@
+use pep440_rs::Version;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::app::{AppExternal, Config, Dependency};
+use crate::error::{Context, LootError};
+use crate::lock::{Lock, LockedPackage};
+use crate::utils::{create_file_with_content, normalize_name};
+
+/// Files other tools can install the locked packages from
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// requirements.txt for pip, with every locked package pinned
+    #[default]
+    Requirements,
+    /// The requirements of lootbox.toml as a PEP 621 pyproject.toml
+    Pyproject,
+    /// PEP 751 pylock.toml
+    Pylock,
+}
+
+/// Writes the lock in another format, without installing anything
+pub async fn export_dependencies(
+    format: &ExportFormat,
+    hashes: &bool,
+    groups: &[String],
+    no_dev: &bool,
+    output: &Option<PathBuf>,
+    app: AppExternal<'_>,
+) -> Result<(), LootError> {
+    if *hashes && *format == ExportFormat::Pyproject {
+        return Err(LootError::config("pyproject.toml has no hashes")
+            .with_hint("Use --format requirements or pylock"));
+    }
+
+    // The venv is not needed, so the config is read without making it
+    let config = std::fs::read_to_string(crate::DEPENDENCIES_FILE).context(|| {
+        LootError::config("There is no lootbox.toml here").with_hint(
+            "Run it inside a project, or create one with loot new {name} {python_version}",
+        )
+    })?;
+    let config: Config =
+        toml::from_str(&config).context(|| LootError::config("lootbox.toml is not valid"))?;
+    let lock = match Lock::read(None)? {
+        Some(lock) if lock.matches(&config) => lock,
+        _ => Lock::resolve(&config, &app).await?,
+    };
+
+    // Every group is exported unless some are chosen explicitly
+    let mut groups: Vec<String> = if *no_dev || !groups.is_empty() {
+        groups.to_vec()
+    } else {
+        config.groups.keys().cloned().collect()
+    };
+    groups.sort();
+
+    let exported = match format {
+        ExportFormat::Requirements => export_requirements(&lock, &groups, *hashes)?,
+        ExportFormat::Pyproject => export_pyproject(&config, &lock, &groups)?,
+        ExportFormat::Pylock => export_pylock(&lock, &groups)?,
+    };
+
+    match output {
+        Some(output) => create_file_with_content(output, exported.as_bytes())
+            .context(|| LootError::config(format!("Error writing {}", output.display())))?,
+        None => print!("{}", exported),
+    }
+    Ok(())
+}
+
+/// Main requirements and the ones of the groups, with the markers each one has in lootbox.toml. Groups get an extra
+/// `'group' in dependency_groups` marker when `group_markers` is set
+fn sections<'a>(
+    lock: &'a Lock,
+    groups: &'a [String],
+    group_markers: bool,
+) -> Result<Vec<(&'a String, Option<String>)>, LootError> {
+    let mut requirements: Vec<(&String, &Dependency, Option<String>)> = lock
+        .requirements
+        .iter()
+        .map(|(name, dependency)| (name, dependency, None))
+        .collect();
+    for group in groups {
+        for (name, dependency) in lock.group(group)? {
+            let marker = group_markers.then(|| format!("'{}' in dependency_groups", group));
+            requirements.push((name, dependency, marker));
+        }
+    }
+
+    Ok(requirements
+        .into_iter()
+        .map(|(name, dependency, group_marker)| {
+            let markers: Vec<String> = dependency
+                .markers()
+                .map(|markers| markers.to_owned())
+                .into_iter()
+                .chain(group_marker)
+                .collect();
+            (name, (!markers.is_empty()).then(|| and(&markers)))
+        })
+        .collect())
+}
+
+fn and(markers: &[String]) -> String {
+    if markers.len() == 1 {
+        return markers[0].clone();
+    }
+    markers
+        .iter()
+        .map(|marker| format!("({})", marker))
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+/// Markers under which each package is needed, `None` if it always is. Only the requirements of lootbox.toml have
+/// markers, any other package is needed whenever something depending on it is
+fn package_markers(
+    packages: &[&LockedPackage],
+    roots: &[(&String, Option<String>)],
+) -> HashMap<String, Option<BTreeSet<String>>> {
+    let mut markers: HashMap<String, Option<BTreeSet<String>>> = HashMap::new();
+    let mut pending: Vec<(String, Option<BTreeSet<String>>)> = roots
+        .iter()
+        .map(|(name, marker)| {
+            let marker = marker.clone().map(|marker| BTreeSet::from([marker]));
+            (normalize_name(name), marker)
+        })
+        .collect();
+
+    while let Some((name, incoming)) = pending.pop() {
+        // Requirements whose markers do not apply to the project are not locked
+        let Some(package) = packages.iter().find(|package| package.name == name) else {
+            continue;
+        };
+
+        let changed = match (markers.get_mut(&name), &incoming) {
+            (None, _) => {
+                markers.insert(name.clone(), incoming.clone());
+                true
+            }
+            (Some(None), _) => false,
+            (Some(current), None) => {
+                *current = None;
+                true
+            }
+            (Some(Some(current)), Some(incoming)) => {
+                let size = current.len();
+                current.extend(incoming.iter().cloned());
+                current.len() != size
+            }
+        };
+        if changed {
+            let marker = markers[&name].clone();
+            for dependency in &package.dependencies {
+                pending.push((dependency.clone(), marker.clone()));
+            }
+        }
+    }
+
+    markers
+}
+
+fn marker_string(markers: Option<&Option<BTreeSet<String>>>) -> Option<String> {
+    let markers = markers.cloned().flatten()?;
+    if markers.len() == 1 {
+        return markers.into_iter().next();
+    }
+    Some(
+        markers
+            .iter()
+            .map(|marker| format!("({})", marker))
+            .collect::<Vec<_>>()
+            .join(" or "),
+    )
+}
+
+fn sorted_packages<'a>(
+    lock: &'a Lock,
+    groups: &[String],
+) -> Result<Vec<&'a LockedPackage>, LootError> {
+    let mut packages = lock.packages_for_groups(groups)?;
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(packages)
+}
+
+/// Url pip can install a git, path or url package from
+fn direct_url(package: &LockedPackage) -> Option<String> {
+    let source = &package.source;
+    if source.starts_with("git+") {
+        Some(source.clone())
+    } else if let Some(path) = source
+        .strip_prefix("path+")
+        .or_else(|| source.strip_prefix("editable+"))
+    {
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        Some(format!("file://{}", path.to_string_lossy()))
+    } else {
+        source.strip_prefix("url+").map(|url| url.to_owned())
+    }
+}
+
+fn export_requirements(lock: &Lock, groups: &[String], hashes: bool) -> Result<String, LootError> {
+    let packages = sorted_packages(lock, groups)?;
+    let markers = package_markers(&packages, &sections(lock, groups, false)?);
+
+    // pip refuses the whole file if any line has no hash, like the ones of git and path packages
+    let unhashed: Vec<&str> = packages
+        .iter()
+        .filter(|package| package.artifacts.is_empty())
+        .map(|package| package.name.as_str())
+        .collect();
+    if hashes && !unhashed.is_empty() {
+        return Err(
+            LootError::config(format!("There are no hashes of {}", unhashed.join(", ")))
+                .with_hint("Export without --hashes, or use --format pylock"),
+        );
+    }
+
+    let mut exported = format!("# Generated by loot export from {}\n", crate::LOCK_FILE);
+    for index in &lock.indexes {
+        let url = index.url.trim_end_matches('/');
+        if packages.iter().any(|package| package.source == url) {
+            let option = if index.default {
+                "--index-url"
+            } else {
+                "--extra-index-url"
+            };
+            exported.push_str(&format!("{} {}\n", option, url));
+        }
+    }
+
+    for package in packages {
+        let mut line = requirement_line(package);
+        if let Some(marker) = marker_string(markers.get(&package.name)) {
+            line.push_str(&format!(" ; {}", marker));
+        }
+        if hashes {
+            for artifact in &package.artifacts {
+                line.push_str(&format!(" \\\n    --hash={}", artifact.hash));
+            }
+        }
+        exported.push_str(&line);
+        exported.push('\n');
+    }
+
+    Ok(exported)
+}
+
+/// Line of a requirements.txt that installs exactly the locked package. Local paths are kept relative so the file
+/// works in other checkouts
+pub fn requirement_line(package: &LockedPackage) -> String {
+    if let Some(path) = package.source.strip_prefix("editable+") {
+        format!("-e {}", relative_path(path))
+    } else if let Some(path) = package.source.strip_prefix("path+") {
+        relative_path(path)
+    } else {
+        match direct_url(package) {
+            Some(url) => format!("{} @ {}", package.name, url),
+            None => format!("{}=={}", package.name, package.version),
+        }
+    }
+}
+
+/// pip only reads a line as a path if it looks like one
+fn relative_path(path: &str) -> String {
+    if path.starts_with('.') || path.starts_with('/') {
+        path.to_owned()
+    } else {
+        format!("./{}", path)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Pyproject {
+    project: PyprojectProject,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    dependency_groups: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct PyprojectProject {
+    name: String,
+    version: String,
+    requires_python: String,
+    dependencies: Vec<String>,
+}
+
+fn export_pyproject(config: &Config, lock: &Lock, groups: &[String]) -> Result<String, LootError> {
+    let packages = sorted_packages(lock, groups)?;
+    let requirement = |name: &String, dependency: &Dependency| {
+        let mut requirement = name.clone();
+        if !dependency.extras().is_empty() {
+            requirement.push_str(&format!("[{}]", dependency.extras().join(",")));
+        }
+
+        let locked = packages
+            .iter()
+            .find(|package| package.name == normalize_name(name));
+        match (
+            dependency.direct_source(),
+            locked.and_then(|package| direct_url(package)),
+        ) {
+            (Some(_), Some(url)) => requirement.push_str(&format!(" @ {}", url)),
+            _ => match dependency.version() {
+                "*" => (),
+                version if Version::from_str(version).is_ok() => {
+                    requirement.push_str(&format!("=={}", version))
+                }
+                version => requirement.push_str(version),
+            },
+        }
+
+        if let Some(markers) = dependency.markers() {
+            requirement.push_str(&format!("; {}", markers));
+        }
+        requirement
+    };
+    let requirements = |requirements: &HashMap<String, Dependency>| {
+        let mut requirements: Vec<String> = requirements
+            .iter()
+            .map(|(name, dependency)| requirement(name, dependency))
+            .collect();
+        requirements.sort();
+        requirements
+    };
+
+    let python_version =
+        Version::from_str(&config.python_version).expect("Error parsing python version");
+    let minor: Vec<String> = python_version
+        .release()
+        .iter()
+        .take(2)
+        .map(|number| number.to_string())
+        .collect();
+
+    let pyproject = Pyproject {
+        project: PyprojectProject {
+            name: config.name.clone(),
+            version: "0.1.0".to_owned(),
+            requires_python: format!(">={}", minor.join(".")),
+            dependencies: requirements(&lock.requirements),
+        },
+        dependency_groups: groups
+            .iter()
+            .map(|group| Ok((group.clone(), requirements(lock.group(group)?))))
+            .collect::<Result<_, LootError>>()?,
+    };
+
+    Ok(toml::to_string_pretty(&pyproject).expect("Error serializing pyproject.toml"))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Pylock {
+    lock_version: String,
+    created_by: String,
+    requires_python: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    dependency_groups: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    default_groups: Vec<String>,
+    packages: Vec<PylockPackage>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct PylockPackage {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    marker: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vcs: Option<PylockVcs>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    directory: Option<PylockDirectory>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archive: Option<PylockFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sdist: Option<PylockFile>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    wheels: Vec<PylockFile>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct PylockVcs {
+    r#type: String,
+    url: String,
+    commit_id: String,
+}
+
+#[derive(Serialize)]
+struct PylockDirectory {
+    path: String,
+    editable: bool,
+}
+
+#[derive(Serialize)]
+struct PylockFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    url: String,
+    hashes: HashMap<String, String>,
+}
+
+impl PylockFile {
+    fn new(url: &str, hash: &str) -> PylockFile {
+        let url = url.split('#').next().unwrap_or(url);
+        let (algorithm, digest) = hash.split_once(':').unwrap_or(("sha256", hash));
+        PylockFile {
+            name: url.rsplit('/').next().map(|name| name.to_owned()),
+            url: url.to_owned(),
+            hashes: HashMap::from([(algorithm.to_owned(), digest.to_owned())]),
+        }
+    }
+}
+
+fn export_pylock(lock: &Lock, groups: &[String]) -> Result<String, LootError> {
+    let packages = sorted_packages(lock, groups)?;
+    let markers = package_markers(&packages, &sections(lock, groups, true)?);
+
+    let pylock_packages = packages
+        .iter()
+        .map(|package| {
+            let mut exported = PylockPackage {
+                name: package.name.clone(),
+                version: Some(package.version.clone()),
+                marker: marker_string(markers.get(&package.name)),
+                index: None,
+                vcs: None,
+                directory: None,
+                archive: None,
+                sdist: None,
+                wheels: Vec::new(),
+            };
+
+            if let Some(git) = package.source.strip_prefix("git+") {
+                let (url, commit) = git.rsplit_once('@').expect("Git sources have a commit");
+                exported.version = None;
+                exported.vcs = Some(PylockVcs {
+                    r#type: "git".to_owned(),
+                    url: url.to_owned(),
+                    commit_id: commit.to_owned(),
+                });
+            } else if let Some(path) = package.source.strip_prefix("editable+") {
+                exported.version = None;
+                exported.directory = Some(PylockDirectory {
+                    path: path.to_owned(),
+                    editable: true,
+                });
+            } else if let Some(path) = package.source.strip_prefix("path+") {
+                exported.version = None;
+                exported.directory = Some(PylockDirectory {
+                    path: path.to_owned(),
+                    editable: false,
+                });
+            } else if package.source.starts_with("url+") {
+                exported.archive = package.artifacts.first().map(|artifact| PylockFile {
+                    name: None,
+                    ..PylockFile::new(&artifact.url, &artifact.hash)
+                });
+            } else {
+                exported.index = Some(package.source.clone());
+                for artifact in &package.artifacts {
+                    let file = PylockFile::new(&artifact.url, &artifact.hash);
+                    if file.url.ends_with(".whl") {
+                        exported.wheels.push(file);
+                    } else if exported.sdist.is_none() {
+                        exported.sdist = Some(file);
+                    }
+                }
+            }
+
+            exported
+        })
+        .collect();
+
+    let pylock = Pylock {
+        lock_version: "1.0".to_owned(),
+        created_by: "loot".to_owned(),
+        requires_python: format!("=={}", lock.python_version),
+        dependency_groups: groups.to_vec(),
+        default_groups: groups.to_vec(),
+        packages: pylock_packages,
+    };
+
+    Ok(toml::to_string_pretty(&pylock).expect("Error serializing pylock.toml"))
+}
@@ -0,0 +1,689 @@
+use inline_colorization::*;
+use pep440_rs::Version;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::app::{AppExternal, Dependency, DetailedDependency, Index};
+use crate::error::{Context, LootError};
+use crate::requirements::RequirementEntry;
+use crate::utils::{create_file_with_content, normalize_name};
+use crate::versions::parse_requirement;
+
+const ARCHIVE_EXTENSIONS: [&str; 5] = [".whl", ".tar.gz", ".zip", ".tgz", ".tar.bz2"];
+
+/// Requirements read from the files of another tool
+#[derive(Default, Debug)]
+struct Imported {
+    /// Group, name and requirement. `None` is the main requirements
+    requirements: Vec<(Option<String>, String, Dependency)>,
+    indexes: Vec<Index>,
+    requires_python: Option<String>,
+    /// Entries that could not be translated, with the reason
+    skipped: Vec<String>,
+}
+
+impl Imported {
+    fn add(
+        &mut self,
+        group: Option<String>,
+        entry: &str,
+        requirement: Result<(String, Dependency), String>,
+    ) {
+        match requirement {
+            Ok((name, dependency)) => self.requirements.push((group, name, dependency)),
+            Err(error) => self.skip(entry, &error),
+        }
+    }
+
+    fn skip(&mut self, entry: &str, reason: &str) {
+        self.skipped.push(format!("{}: {}", entry, reason));
+    }
+}
+
+/// Adds the requirements of a requirements.txt, pyproject.toml or Pipfile to lootbox.toml
+pub async fn import_dependencies(
+    file: &Path,
+    group: &Option<String>,
+    mut app: AppExternal<'_>,
+) -> Result<(), LootError> {
+    app.make_internal(None).await?;
+    let mut config = app.app_config.clone().expect("Config file does not exist");
+
+    let mut imported = Imported::default();
+    match file.file_name().and_then(|name| name.to_str()) {
+        Some("pyproject.toml") => import_pyproject(file, &mut imported)?,
+        Some("Pipfile") => import_pipfile(file, &mut imported)?,
+        _ => import_requirements_txt(file, &mut imported)?,
+    }
+
+    if let Some(requires_python) = &imported.requires_python {
+        let python_version =
+            Version::from_str(&config.python_version).expect("Error parsing python version");
+        match parse_requirement(requires_python) {
+            Ok(specifiers) if !specifiers.contains(&python_version) => println!(
+                "{color_yellow}{} requires python {}, the project uses {}{color_reset}",
+                file.display(),
+                requires_python,
+                config.python_version
+            ),
+            Ok(_) => (),
+            Err(error) => imported.skip(&format!("python {}", requires_python), &error.to_string()),
+        }
+    }
+
+    // Imported requirements replace the ones already in lootbox.toml
+    let count = imported.requirements.len();
+    for (requirement_group, name, dependency) in imported.requirements {
+        let requirements = config.group_requirements_mut(&requirement_group.or(group.clone()));
+        requirements.retain(|existing, _| normalize_name(existing) != normalize_name(&name));
+        requirements.insert(name, dependency);
+    }
+    let has_default = config.indexes.iter().any(|index| index.default);
+    for mut index in imported.indexes {
+        if config
+            .indexes
+            .iter()
+            .any(|existing| existing.name == index.name)
+        {
+            continue;
+        }
+        index.default = index.default && !has_default;
+        config.indexes.push(index);
+    }
+
+    create_file_with_content(
+        &PathBuf::from(crate::DEPENDENCIES_FILE),
+        toml::to_string_pretty(&config)
+            .expect("Could not convert to toml")
+            .as_bytes(),
+    )
+    .context(|| LootError::config("Error writing to lootbox.toml"))?;
+
+    println!(
+        "{color_green}Imported {} requirements from {}{color_reset}",
+        count,
+        file.display()
+    );
+    if !imported.skipped.is_empty() {
+        println!("{color_yellow}Could not import:{color_reset}");
+        for skipped in &imported.skipped {
+            println!("  {color_yellow}{}{color_reset}", skipped);
+        }
+    }
+    Ok(())
+}
+
+fn read_file(file: &Path) -> Result<String, LootError> {
+    std::fs::read_to_string(file)
+        .context(|| LootError::config(format!("Could not read {}", file.display())))
+}
+
+fn read_toml(file: &Path) -> Result<toml::Table, LootError> {
+    toml::from_str(&read_file(file)?)
+        .context(|| LootError::config(format!("{} is not valid", file.display())))
+}
+
+fn import_requirements_txt(file: &Path, imported: &mut Imported) -> Result<(), LootError> {
+    let mut requirements = Vec::new();
+    let mut constraints = HashMap::new();
+    read_requirements_file(file, false, &mut requirements, &mut constraints, imported)?;
+
+    // Constraints only narrow the versions of packages that are required
+    for (name, dependency) in requirements {
+        let dependency = match constraints.get(&normalize_name(&name)) {
+            Some(constraint) if dependency.direct_source().is_none() => {
+                let version = match (dependency.version(), constraint) {
+                    ("*", constraint) => constraint.to_owned(),
+                    (version, constraint) if constraint == "*" => version.to_owned(),
+                    (version, constraint) => {
+                        format!("{},{}", specifiers(version), specifiers(constraint))
+                    }
+                };
+                dependency.with_version(version)
+            }
+            _ => dependency,
+        };
+        imported.requirements.push((None, name, dependency));
+    }
+    Ok(())
+}
+
+/// A bare version is an exact pin in lootbox.toml but not when it is combined with other specifiers
+fn specifiers(version: &str) -> String {
+    match Version::from_str(version) {
+        Ok(_) => format!("=={}", version),
+        Err(_) => version.to_owned(),
+    }
+}
+
+/// Reads a requirements file and the ones it includes. Constraints files only give versions
+fn read_requirements_file(
+    file: &Path,
+    is_constraints: bool,
+    requirements: &mut Vec<(String, Dependency)>,
+    constraints: &mut HashMap<String, String>,
+    imported: &mut Imported,
+) -> Result<(), LootError> {
+    let directory = file.parent().unwrap_or(Path::new("")).to_path_buf();
+    // Lines ending with a backslash continue on the next one
+    let content = read_file(file)?.replace("\\\r\n", " ").replace("\\\n", " ");
+
+    for line in content.lines() {
+        // Comments need whitespace before the #, the fragment of an url is not a comment
+        let line = match line.find(" #") {
+            Some(start) => &line[..start],
+            None if line.trim_start().starts_with('#') => "",
+            None => line,
+        }
+        .trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('-') {
+            let (option, value) = line
+                .split_once(|c: char| c == '=' || c.is_whitespace())
+                .map(|(option, value)| (option, value.trim()))
+                .unwrap_or((line, ""));
+            match option {
+                "-r" | "--requirement" => read_requirements_file(
+                    &directory.join(value),
+                    is_constraints,
+                    requirements,
+                    constraints,
+                    imported,
+                )?,
+                "-c" | "--constraint" => read_requirements_file(
+                    &directory.join(value),
+                    true,
+                    requirements,
+                    constraints,
+                    imported,
+                )?,
+                "-e" | "--editable" if !is_constraints => match editable_requirement(value) {
+                    Ok(requirement) => requirements.push(requirement),
+                    Err(error) => imported.skip(line, &error),
+                },
+                _ => imported.skip(line, "pip options are not imported"),
+            }
+            continue;
+        }
+
+        // Hashes and other per requirement options are not kept
+        let requirement = line.split(" --").next().unwrap_or(line).trim();
+        match requirement_line(requirement) {
+            Ok((name, dependency)) if is_constraints => {
+                constraints.insert(normalize_name(&name), dependency.version().to_owned());
+            }
+            Ok(requirement) => requirements.push(requirement),
+            Err(error) => imported.skip(line, &error),
+        }
+    }
+    Ok(())
+}
+
+/// A PEP 508 requirement, a direct reference `name @ url`, or a url or path with the name in an `#egg=` fragment
+pub fn requirement_line(line: &str) -> Result<(String, Dependency), String> {
+    let (requirement, markers) = match line.split_once(';') {
+        Some((requirement, markers)) => (requirement.trim(), Some(markers.trim().to_owned())),
+        None => (line.trim(), None),
+    };
+
+    let reference = match requirement.split_once('@') {
+        Some((name, url)) if url.contains("://") && !name.contains("://") => {
+            Some((Some(name.trim()), url.trim()))
+        }
+        _ if requirement.contains("://") || requirement.starts_with('.') => {
+            Some((None, requirement))
+        }
+        _ => None,
+    };
+
+    match reference {
+        Some((name, url)) => {
+            let mut entry = match name {
+                Some(name) => RequirementEntry::parse(name)?,
+                None => RequirementEntry::parse(&reference_name(url)?)?,
+            };
+            entry.markers = markers;
+            let dependency = Dependency::Detailed(DetailedDependency {
+                extras: entry.extras,
+                markers: entry.markers,
+                ..direct_source(url)
+            });
+            Ok((entry.name, dependency))
+        }
+        None => {
+            let entry = RequirementEntry::parse(line)?;
+            Ok((entry.name.clone(), entry.into_dependency()))
+        }
+    }
+}
+
+/// Name of a url or path requirement, from its `#egg=` fragment or the pyproject.toml of a local directory
+fn reference_name(url: &str) -> Result<String, String> {
+    if let Some((_, fragment)) = url.split_once('#') {
+        if let Some(name) = fragment
+            .split('&')
+            .find_map(|part| part.strip_prefix("egg="))
+        {
+            return Ok(name.to_owned());
+        }
+    }
+
+    let path = url.split('#').next().unwrap_or(url);
+    let path = PathBuf::from(path.strip_prefix("file://").unwrap_or(path));
+    let name = std::fs::read_to_string(path.join("pyproject.toml"))
+        .ok()
+        .and_then(|pyproject| toml::from_str::<toml::Table>(&pyproject).ok())
+        .and_then(|pyproject| {
+            pyproject
+                .get("project")?
+                .get("name")?
+                .as_str()
+                .map(|name| name.to_owned())
+        });
+    name.ok_or_else(|| "the package name is unknown, add #egg={name}".to_owned())
+}
+
+/// Git, path or url source of a direct reference
+fn direct_source(url: &str) -> DetailedDependency {
+    let url = url.split('#').next().unwrap_or(url);
+
+    if let Some(git) = url.strip_prefix("git+") {
+        // The revision is after the last @, as long as it is not the user of the host
+        let (git, rev) = match git.rsplit_once('@') {
+            Some((repository, rev)) if repository.contains("://") && !rev.contains('/') => {
+                (repository, Some(rev.to_owned()))
+            }
+            _ => (git, None),
+        };
+        return DetailedDependency {
+            git: Some(git.to_owned()),
+            rev,
+            ..Default::default()
+        };
+    }
+
+    let is_archive = ARCHIVE_EXTENSIONS
+        .iter()
+        .any(|extension| url.ends_with(extension));
+    match url.strip_prefix("file://") {
+        Some(path) if !is_archive => DetailedDependency {
+            path: Some(path.to_owned()),
+            ..Default::default()
+        },
+        None if !url.contains("://") => DetailedDependency {
+            path: Some(url.to_owned()),
+            ..Default::default()
+        },
+        _ => DetailedDependency {
+            url: Some(url.to_owned()),
+            ..Default::default()
+        },
+    }
+}
+
+/// `-e path` or `-e git+url#egg=name`. Only local directories can be installed in editable mode
+fn editable_requirement(value: &str) -> Result<(String, Dependency), String> {
+    let name = reference_name(value)?;
+    let source = direct_source(value);
+    let dependency = match source.path {
+        Some(_) => DetailedDependency {
+            editable: true,
+            ..source
+        },
+        None => source,
+    };
+
+    Ok((name, Dependency::Detailed(dependency)))
+}
+
+fn import_pyproject(file: &Path, imported: &mut Imported) -> Result<(), LootError> {
+    let pyproject = read_toml(file)?;
+
+    // PEP 621
+    if let Some(project) = pyproject.get("project") {
+        imported.requires_python = project
+            .get("requires-python")
+            .and_then(|requires_python| requires_python.as_str())
+            .map(|requires_python| requires_python.to_owned());
+
+        for requirement in string_array(project.get("dependencies")) {
+            imported.add(None, &requirement, requirement_line(&requirement));
+        }
+        // Extras of the project become groups
+        let optional = project
+            .get("optional-dependencies")
+            .and_then(|optional| optional.as_table());
+        for (extra, requirements) in optional.into_iter().flatten() {
+            for requirement in string_array(Some(requirements)) {
+                imported.add(
+                    Some(extra.clone()),
+                    &requirement,
+                    requirement_line(&requirement),
+                );
+            }
+        }
+    }
+
+    // PEP 735
+    let dependency_groups = pyproject
+        .get("dependency-groups")
+        .and_then(|groups| groups.as_table());
+    for (group, requirements) in dependency_groups.into_iter().flatten() {
+        for requirement in requirements.as_array().into_iter().flatten() {
+            match requirement.as_str() {
+                Some(requirement) => imported.add(
+                    Some(group.clone()),
+                    requirement,
+                    requirement_line(requirement),
+                ),
+                None => imported.skip(
+                    &format!("dependency-groups.{} {}", group, requirement),
+                    "included groups are not imported",
+                ),
+            }
+        }
+    }
+
+    if let Some(poetry) = pyproject
+        .get("tool")
+        .and_then(|tool| tool.get("poetry"))
+        .and_then(|poetry| poetry.as_table())
+    {
+        import_poetry(poetry, imported);
+    }
+    Ok(())
+}
+
+fn string_array(value: Option<&toml::Value>) -> Vec<String> {
+    value
+        .and_then(|value| value.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|item| item.as_str().map(|item| item.to_owned()))
+        .collect()
+}
+
+fn import_poetry(poetry: &toml::Table, imported: &mut Imported) {
+    let mut sections: Vec<(Option<String>, &toml::Value)> = Vec::new();
+    if let Some(dependencies) = poetry.get("dependencies") {
+        sections.push((None, dependencies));
+    }
+    if let Some(dependencies) = poetry.get("dev-dependencies") {
+        sections.push((Some("dev".to_owned()), dependencies));
+    }
+    let groups = poetry.get("group").and_then(|groups| groups.as_table());
+    for (group, table) in groups.into_iter().flatten() {
+        if let Some(dependencies) = table.get("dependencies") {
+            sections.push((Some(group.clone()), dependencies));
+        }
+    }
+
+    for (group, dependencies) in sections {
+        for (name, value) in dependencies.as_table().into_iter().flatten() {
+            // requires-python of PEP 621 comes first
+            if name == "python" {
+                match value.as_str().map(poetry_version) {
+                    Some(Ok(version)) => {
+                        imported.requires_python.get_or_insert(specifiers(&version));
+                    }
+                    Some(Err(error)) => imported.skip(&format!("python = {}", value), &error),
+                    None => imported.skip(&format!("python = {}", value), "expected a string"),
+                }
+                continue;
+            }
+
+            let entry = format!("{} = {}", name, value);
+            imported.add(group.clone(), &entry, poetry_dependency(name, value));
+        }
+    }
+
+    let sources = poetry.get("source").and_then(|sources| sources.as_array());
+    for source in sources.into_iter().flatten() {
+        let (Some(name), Some(url)) = (
+            source.get("name").and_then(|name| name.as_str()),
+            source.get("url").and_then(|url| url.as_str()),
+        ) else {
+            continue;
+        };
+        let priority = source
+            .get("priority")
+            .and_then(|priority| priority.as_str());
+        imported.indexes.push(Index {
+            name: name.to_owned(),
+            url: url.to_owned(),
+            default: matches!(priority, Some("primary") | Some("default"))
+                || source.get("default").and_then(|default| default.as_bool()) == Some(true),
+        });
+    }
+}
+
+fn poetry_dependency(name: &str, value: &toml::Value) -> Result<(String, Dependency), String> {
+    if let Some(version) = value.as_str() {
+        return Ok((
+            name.to_owned(),
+            Dependency::Version(poetry_version(version)?),
+        ));
+    }
+    let table = value
+        .as_table()
+        .ok_or("several constraints for one package are not supported")?;
+    let string = |key: &str| {
+        table
+            .get(key)
+            .and_then(|value| value.as_str())
+            .map(|value| value.to_owned())
+    };
+
+    let version = string("version")
+        .map(|version| poetry_version(&version))
+        .transpose()?;
+    let mut markers = string("markers");
+    if let Some(python) = string("python") {
+        let python_markers = python_markers(&poetry_version(&python)?);
+        markers = Some(match markers {
+            Some(markers) => format!("({}) and {}", markers, python_markers),
+            None => python_markers,
+        });
+    }
+
+    Ok((
+        name.to_owned(),
+        Dependency::Detailed(DetailedDependency {
+            version,
+            extras: string_array(table.get("extras")),
+            markers,
+            git: string("git"),
+            rev: string("rev").or(string("tag")).or(string("branch")),
+            editable: table.get("develop").and_then(|develop| develop.as_bool()) == Some(true),
+            path: string("path"),
+            url: string("url"),
+            index: string("source"),
+        }),
+    ))
+}
+
+/// Translates a Poetry version constraint, with its `^` and `~` operators, to PEP 440
+fn poetry_version(constraint: &str) -> Result<String, String> {
+    if constraint.contains("||") {
+        return Err("alternatives with || are not supported".to_owned());
+    }
+
+    // Constraints are separated by commas or spaces, and `>= 1.2` has a space after the operator
+    let mut parts: Vec<String> = Vec::new();
+    for token in constraint
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|token| !token.is_empty())
+    {
+        match parts.last_mut() {
+            Some(last) if last.chars().all(|c| "<>=!~^".contains(c)) => last.push_str(token),
+            _ => parts.push(token.to_owned()),
+        }
+    }
+
+    let mut specifiers = Vec::new();
+    for part in parts {
+        if part == "*" {
+            continue;
+        }
+        let specifier = if let Some(version) = part.strip_prefix('^') {
+            caret_range(version)?
+        } else if let Some(version) = part.strip_prefix('~').filter(|_| !part.starts_with("~=")) {
+            tilde_range(version)?
+        } else if part.starts_with(|c: char| c.is_ascii_digit()) {
+            format!("=={}", part)
+        } else {
+            part
+        };
+        specifiers.push(specifier);
+    }
+
+    let version = match specifiers.as_slice() {
+        [] => "*".to_owned(),
+        [exact] if exact.starts_with("==") && !exact.ends_with('*') => exact[2..].to_owned(),
+        _ => specifiers.join(","),
+    };
+    parse_requirement(&version).map_err(|error| error.to_string())?;
+    Ok(version)
+}
+
+/// `^1.2.3` allows anything up to the next change of the first number that is not 0
+fn caret_range(version: &str) -> Result<String, String> {
+    let release = release_numbers(version)?;
+    let position = release
+        .iter()
+        .position(|number| *number != 0)
+        .unwrap_or(release.len() - 1);
+    Ok(bumped_range(version, &release, position))
+}
+
+/// `~1.2.3` allows patch releases, `~1` minor releases too
+fn tilde_range(version: &str) -> Result<String, String> {
+    let release = release_numbers(version)?;
+    let position = if release.len() > 1 { 1 } else { 0 };
+    Ok(bumped_range(version, &release, position))
+}
+
+fn release_numbers(version: &str) -> Result<Vec<u64>, String> {
+    Version::from_str(version)
+        .map(|version| version.release().to_vec())
+        .map_err(|error| error.to_string())
+}
+
+fn bumped_range(version: &str, release: &[u64], position: usize) -> String {
+    let mut upper = release[..position].to_vec();
+    upper.push(release[position] + 1);
+    format!(">={},<{}", version, Version::new(upper))
+}
+
+/// Markers limiting a requirement to the python versions of the specifiers
+fn python_markers(specifiers: &str) -> String {
+    let specifiers = if Version::from_str(specifiers).is_ok() {
+        format!("=={}", specifiers)
+    } else {
+        specifiers.to_owned()
+    };
+
+    specifiers
+        .split(',')
+        .map(|specifier| {
+            let version_start = specifier
+                .find(|c: char| c.is_ascii_digit())
+                .unwrap_or(specifier.len());
+            format!(
+                "python_version {} '{}'",
+                &specifier[..version_start],
+                &specifier[version_start..]
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+fn import_pipfile(file: &Path, imported: &mut Imported) -> Result<(), LootError> {
+    let pipfile = read_toml(file)?;
+
+    let sources = pipfile.get("source").and_then(|sources| sources.as_array());
+    for (position, source) in sources.into_iter().flatten().enumerate() {
+        let (Some(name), Some(url)) = (
+            source.get("name").and_then(|name| name.as_str()),
+            source.get("url").and_then(|url| url.as_str()),
+        ) else {
+            continue;
+        };
+        if url.trim_end_matches('/') == crate::versions::PYPI_INDEX {
+            continue;
+        }
+        // Pipenv looks packages up in the first source
+        imported.indexes.push(Index {
+            name: name.to_owned(),
+            url: url.to_owned(),
+            default: position == 0,
+        });
+    }
+
+    let requires = pipfile.get("requires");
+    if let Some(python) = requires
+        .and_then(|requires| requires.get("python_full_version"))
+        .and_then(|python| python.as_str())
+    {
+        imported.requires_python = Some(format!("=={}", python));
+    } else if let Some(python) = requires
+        .and_then(|requires| requires.get("python_version"))
+        .and_then(|python| python.as_str())
+    {
+        imported.requires_python = Some(format!("=={}.*", python));
+    }
+
+    for (section, group) in [("packages", None), ("dev-packages", Some("dev".to_owned()))] {
+        let packages = pipfile
+            .get(section)
+            .and_then(|packages| packages.as_table());
+        for (name, value) in packages.into_iter().flatten() {
+            let entry = format!("{} = {}", name, value);
+            imported.add(group.clone(), &entry, pipfile_dependency(name, value));
+        }
+    }
+    Ok(())
+}
+
+fn pipfile_dependency(name: &str, value: &toml::Value) -> Result<(String, Dependency), String> {
+    let version = |version: &str| {
+        RequirementEntry::parse(&format!("{} {}", name, version))
+            .map(|entry| entry.version.unwrap_or_else(|| "*".to_owned()))
+    };
+
+    if let Some(requirement) = value.as_str() {
+        return Ok((name.to_owned(), Dependency::Version(version(requirement)?)));
+    }
+    let table = value.as_table().ok_or("expected a version or a table")?;
+    let string = |key: &str| {
+        table
+            .get(key)
+            .and_then(|value| value.as_str())
+            .map(|value| value.to_owned())
+    };
+
+    Ok((
+        name.to_owned(),
+        Dependency::Detailed(DetailedDependency {
+            version: string("version")
+                .map(|requirement| version(&requirement))
+                .transpose()?,
+            extras: string_array(table.get("extras")),
+            markers: string("markers"),
+            git: string("git"),
+            rev: string("ref"),
+            editable: table
+                .get("editable")
+                .and_then(|editable| editable.as_bool())
+                == Some(true),
+            path: string("path"),
+            url: string("file"),
+            index: string("index"),
+        }),
+    ))
+}
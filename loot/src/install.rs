@@ -1,9 +1,12 @@
 use inline_colorization::*;
 use std::fs;
-use std::io::{BufWriter, Cursor};
-use std::path::PathBuf;
+use std::io::{BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
 
 use crate::app::AppExternal;
+use crate::commands::CommandOutput;
+use crate::error::{Context, LootError};
+use crate::shutdown::partial_dir;
 use crate::PYTHON_INSTALLS_DIRECTORY;
 
 #[cfg(target_os = "windows")]
@@ -15,124 +18,193 @@ pub async fn install_python_version(
     version_to_install: &String,
     force: &bool,
     app: AppExternal<'_>,
-) {
+) -> Result<(), LootError> {
     let install_path = app
         .data_path
         .join(PYTHON_INSTALLS_DIRECTORY)
         .join(version_to_install);
 
-    fs::create_dir_all(&install_path).expect("Couldn't create new install directory");
+    let install_dir_error = || {
+        LootError::interpreter(format!(
+            "Error preparing the install dir {}",
+            install_path.display()
+        ))
+    };
+    fs::create_dir_all(&install_path).context(install_dir_error)?;
     if *force {
-        fs::remove_dir_all(&install_path).expect("Couldn't delete previous instalation");
-
-        fs::create_dir_all(&install_path).expect("Couldn't create new install directory");
-    } else {
-        if fs::read_dir(&install_path)
-            .expect("Couldn't read installation dir")
-            .next()
-            .is_some()
-        {
-            panic!("Install directory is not empty, use --force to override previous install");
-        }
+        fs::remove_dir_all(&install_path).context(install_dir_error)?;
+
+        fs::create_dir_all(&install_path).context(install_dir_error)?;
+    } else if fs::read_dir(&install_path)
+        .context(install_dir_error)?
+        .next()
+        .is_some()
+    {
+        return Err(LootError::interpreter(format!(
+            "Python {} is already installed",
+            version_to_install
+        ))
+        .with_hint("Use --force to install it again"));
     }
 
-    install_python(install_path, version_to_install, &app).await;
+    let _partial = partial_dir(&install_path);
+    install_python(install_path, version_to_install, &app).await
 }
 
 #[cfg(target_os = "windows")]
-async fn install_python(install_path: PathBuf, version_to_install: &String, app: &AppExternal<'_>) {
+async fn install_python(
+    install_path: PathBuf,
+    version_to_install: &String,
+    app: &AppExternal<'_>,
+) -> Result<(), LootError> {
     let download_url = "https://dist.nuget.org/win-x86-commandline/latest/nuget.exe";
 
     let installer_content_response = reqwest::get(download_url)
         .await
-        .expect("Error requesting python version");
+        .context(|| download_error(download_url))?;
 
     if !installer_content_response.status().is_success() {
-        panic!("NuGet does not exist!?, The url might have moved");
+        return Err(LootError::network(format!(
+            "NuGet is not at {} anymore, the url might have moved",
+            download_url
+        )));
     }
-    let response_bytes = installer_content_response.bytes();
-
-    let mut installer_file = BufWriter::new(
-        fs::File::create(install_path.join(PYTHON_INSTALLER_NAME))
-            .expect("Couldn't create installer file"),
-    );
-
-    std::io::copy(
-        &mut Cursor::new(
-            response_bytes
-                .await
-                .expect("NuGet does not exist!?, The url might have moved"),
-        ),
-        &mut installer_file,
-    )
-    .expect("Couldn't copy installer contents");
-    drop(installer_file);
-
-    let install_version_command = format!(
-        "{} install python -Version {} -OutputDirectory {}",
-        install_path.join(PYTHON_INSTALLER_NAME).to_string_lossy(),
-        version_to_install,
-        install_path.to_string_lossy()
-    );
-    app.run_external_command(install_version_command).await;
-
-    println!("{color_bright_yellow}Finished queuing commands for python installation{color_reset}");
+    let response_bytes = installer_content_response
+        .bytes()
+        .await
+        .context(|| download_error(download_url))?;
+
+    write_installer(&install_path, &response_bytes)?;
+
+    let install_version_command = vec![
+        install_path
+            .join(PYTHON_INSTALLER_NAME)
+            .to_string_lossy()
+            .to_string(),
+        "install".to_owned(),
+        "python".to_owned(),
+        "-Version".to_owned(),
+        version_to_install.to_owned(),
+        "-OutputDirectory".to_owned(),
+        install_path.to_string_lossy().to_string(),
+    ];
+    check_step(
+        app.run_external_command(install_version_command).await,
+        "Installing python with NuGet",
+    )?;
+
+    println!("{color_bright_yellow}Finished python installation{color_reset}");
+    Ok(())
 }
 
 #[cfg(not(target_os = "windows"))]
-async fn install_python(install_path: PathBuf, version_to_install: &String, app: &AppExternal<'_>) {
+async fn install_python(
+    install_path: PathBuf,
+    version_to_install: &String,
+    app: &AppExternal<'_>,
+) -> Result<(), LootError> {
     // Download
     let download_url = format!(
         "https://www.python.org/ftp/python/{version_to_install}/Python-{version_to_install}.tgz"
     );
 
-    let installer_content_response = reqwest::get(download_url)
+    let installer_content_response = reqwest::get(&download_url)
         .await
-        .expect("Error requesting python version");
+        .context(|| download_error(&download_url))?;
 
     if !installer_content_response.status().is_success() {
-        panic!("Python version does not exist. Write the pythons version complete name (3.10.0)");
+        return Err(LootError::interpreter(format!(
+            "Python {} does not exist",
+            version_to_install
+        ))
+        .with_hint("Write the complete version, like 3.10.0"));
     }
-    let response_bytes = installer_content_response.bytes();
-
-    let mut installer_file = BufWriter::new(
-        fs::File::create(install_path.join(PYTHON_INSTALLER_NAME))
-            .expect("Couldn't create installer file"),
-    );
+    let response_bytes = installer_content_response
+        .bytes()
+        .await
+        .context(|| download_error(&download_url))?;
 
-    std::io::copy(
-        &mut Cursor::new(response_bytes.await.expect("Python version Not found")),
-        &mut installer_file,
-    )
-    .expect("Couldn't copy installer contents");
-    drop(installer_file);
+    write_installer(&install_path, &response_bytes)?;
 
     // Decompress
-    let install_version_command = format!(
-        "tar -xf {} -C {}",
-        install_path.join(PYTHON_INSTALLER_NAME).to_string_lossy(),
-        install_path.to_string_lossy()
-    );
-    app.run_external_command(install_version_command).await;
+    let install_version_command = vec![
+        "tar".to_owned(),
+        "-xf".to_owned(),
+        install_path
+            .join(PYTHON_INSTALLER_NAME)
+            .to_string_lossy()
+            .to_string(),
+        "-C".to_owned(),
+        install_path.to_string_lossy().to_string(),
+    ];
+    check_step(
+        app.run_external_command(install_version_command).await,
+        "Decompressing python",
+    )?;
 
     // Configure. Those clone calls are not the bottleneck, so no need to do lifetimes. The building from python (In another thread) is the bottleneck for this part
     let source_directory_name = format!("Python-{version_to_install}");
     let python_source_path = install_path.join(source_directory_name);
 
-    let configure_command = format!(
-        "{} --enable-optimizations --prefix={}",
-        python_source_path.join("configure").to_string_lossy(),
-        install_path.to_string_lossy()
-    );
-    app.run_external_command_from_dir(configure_command, python_source_path.clone())
-        .await;
+    let configure_command = vec![
+        python_source_path
+            .join("configure")
+            .to_string_lossy()
+            .to_string(),
+        "--enable-optimizations".to_owned(),
+        format!("--prefix={}", install_path.to_string_lossy()),
+    ];
+    check_step(
+        app.run_external_command_from_dir(configure_command, python_source_path.clone())
+            .await,
+        "configure",
+    )?;
 
     // Make and install
-    app.run_external_command_from_dir("make".to_owned(), python_source_path.clone())
-        .await;
+    check_step(
+        app.run_external_command_from_dir(
+            crate::commands::args(&["make"]),
+            python_source_path.clone(),
+        )
+        .await,
+        "make",
+    )?;
+    check_step(
+        app.run_external_command_from_dir(
+            crate::commands::args(&["make", "install"]),
+            python_source_path,
+        )
+        .await,
+        "make install",
+    )?;
+
+    println!("{color_bright_yellow}Finished python installation{color_reset}");
+    Ok(())
+}
 
-    app.run_external_command_from_dir("make install".to_owned(), python_source_path)
-        .await;
+fn download_error(url: &str) -> LootError {
+    LootError::network(format!("Error downloading {}", url))
+        .with_hint("Check the internet connection and try again")
+}
 
-    println!("{color_bright_yellow}Finished queuing commands for python installation{color_reset}");
+fn write_installer(install_path: &Path, content: &[u8]) -> Result<(), LootError> {
+    let installer_error = || LootError::interpreter("Error writing the installer");
+    let mut installer_file = BufWriter::new(
+        fs::File::create(install_path.join(PYTHON_INSTALLER_NAME)).context(installer_error)?,
+    );
+
+    std::io::copy(&mut Cursor::new(content), &mut installer_file).context(installer_error)?;
+    installer_file.flush().context(installer_error)
+}
+
+/// Stops the installation at the first step that fails
+fn check_step(output: CommandOutput, step: &str) -> Result<(), LootError> {
+    if output.success() {
+        Ok(())
+    } else {
+        Err(LootError::interpreter(format!(
+            "{} failed with {}",
+            step, output.status
+        )))
+    }
 }
@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::app::{AppExternal, Config, Dependency, Index, ResolutionStrategy};
+use crate::error::{Context, LootError};
+use crate::requirements::{MarkerEnvironment, Requirement};
+use crate::resolver::{ProjectRequirement, Resolver};
+use crate::sources::{fetch_direct_package, DirectPackage};
+use crate::utils::{create_file_with_content, normalize_name};
+
+/// Fully resolved dependency graph of a project, written next to lootbox.toml
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct Lock {
+    /// Inputs the lock was resolved from. If they differ from the config the lock is outdated
+    pub python_version: String,
+    pub requirements: HashMap<String, Dependency>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub groups: HashMap<String, HashMap<String, Dependency>>,
+    #[serde(default, rename = "index", skip_serializing_if = "Vec::is_empty")]
+    pub indexes: Vec<Index>,
+    #[serde(default, skip_serializing_if = "ResolutionStrategy::is_highest")]
+    pub resolution: ResolutionStrategy,
+
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    /// Index the package comes from, or a git, path or url source
+    pub source: String,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(default, rename = "artifact")]
+    pub artifacts: Vec<Artifact>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct Artifact {
+    pub url: String,
+    pub hash: String,
+}
+
+impl Lock {
+    /// Reads the project lock file, `None` if there is none yet
+    pub fn read(path: Option<std::path::PathBuf>) -> Result<Option<Lock>, LootError> {
+        let location = path.unwrap_or_default();
+
+        Self::read_from(&location.join(crate::LOCK_FILE)).context(|| {
+            LootError::config(format!("{} is not valid", crate::LOCK_FILE))
+                .with_hint("Fix it, or delete it and run loot sync to resolve it again")
+        })
+    }
+
+    /// Reads the lock of the packages currently installed in the venv
+    pub fn read_installed(path: Option<std::path::PathBuf>) -> Result<Option<Lock>, LootError> {
+        let location = path.unwrap_or_default();
+
+        Self::read_from(&location.join(".lootbox").join(crate::LOCK_FILE)).context(|| {
+            LootError::venv(format!(".lootbox/{} is not valid", crate::LOCK_FILE))
+                .with_hint("Delete .lootbox and run loot sync to make the venv again")
+        })
+    }
+
+    fn read_from(path: &std::path::Path) -> Result<Option<Lock>, toml::de::Error> {
+        let Ok(lock) = std::fs::read_to_string(path) else {
+            return Ok(None);
+        };
+
+        toml::from_str(&lock).map(Some)
+    }
+
+    pub fn write(&self, path: Option<std::path::PathBuf>) -> Result<(), LootError> {
+        let location = path.unwrap_or_default();
+
+        self.write_to(&location.join(crate::LOCK_FILE))
+    }
+
+    pub fn write_installed(&self, path: Option<std::path::PathBuf>) -> Result<(), LootError> {
+        let location = path.unwrap_or_default();
+
+        self.write_to(&location.join(".lootbox").join(crate::LOCK_FILE))
+    }
+
+    fn write_to(&self, path: &std::path::Path) -> Result<(), LootError> {
+        create_file_with_content(
+            &path.to_path_buf(),
+            toml::to_string_pretty(self)
+                .expect("Error serializing lock file")
+                .as_bytes(),
+        )
+        .context(|| LootError::config(format!("Error writing {}", path.display())))
+    }
+
+    pub fn matches(&self, config: &Config) -> bool {
+        self.python_version == config.python_version
+            && self.requirements == config.requirements
+            && self.groups == config.groups
+            && self.indexes == config.indexes
+            && self.resolution == config.resolution
+    }
+
+    /// Requirements of a group, which has to be in lootbox.toml
+    pub fn group(&self, group: &str) -> Result<&HashMap<String, Dependency>, LootError> {
+        self.groups.get(group).context(|| {
+            let mut groups: Vec<&String> = self.groups.keys().collect();
+            groups.sort();
+            let hint = if groups.is_empty() {
+                "lootbox.toml has no groups".to_owned()
+            } else {
+                let groups: Vec<&str> = groups.iter().map(|group| group.as_str()).collect();
+                format!("The groups in lootbox.toml are {}", groups.join(", "))
+            };
+            LootError::config(format!("Group {} does not exist", group)).with_hint(hint)
+        })
+    }
+
+    /// Packages needed by the main requirements and the given groups
+    pub fn packages_for_groups(&self, groups: &[String]) -> Result<Vec<&LockedPackage>, LootError> {
+        let mut pending: Vec<String> = self
+            .requirements
+            .keys()
+            .map(|name| normalize_name(name))
+            .collect();
+        for group in groups {
+            pending.extend(self.group(group)?.keys().map(|name| normalize_name(name)));
+        }
+
+        let mut needed = HashSet::new();
+        while let Some(name) = pending.pop() {
+            // Requirements whose markers do not apply are not part of the lock
+            let Some(package) = self.packages.iter().find(|package| package.name == name) else {
+                continue;
+            };
+
+            if needed.insert(name) {
+                pending.extend(package.dependencies.iter().cloned());
+            }
+        }
+
+        Ok(self
+            .packages
+            .iter()
+            .filter(|package| needed.contains(&package.name))
+            .collect())
+    }
+
+    /// Returns the project lock, resolving the config again only if the lock is missing or outdated
+    pub async fn get_or_resolve(
+        path: Option<std::path::PathBuf>,
+        config: &Config,
+        app: &AppExternal<'_>,
+    ) -> Result<Lock, LootError> {
+        let old_lock = Self::read(path.clone())?;
+        if let Some(lock) = &old_lock {
+            if lock.matches(config) {
+                return Ok(lock.clone());
+            }
+        }
+
+        println!("Resolving dependencies");
+        let preferred = old_lock.map(|lock| lock.packages).unwrap_or_default();
+        let lock = Self::resolve_preferring(config, app, &preferred).await?;
+        lock.write(path)?;
+        Ok(lock)
+    }
+
+    pub async fn resolve(config: &Config, app: &AppExternal<'_>) -> Result<Lock, LootError> {
+        Self::resolve_preferring(config, app, &[]).await
+    }
+
+    /// Same as `resolve`, but the given packages keep their version if the requirements still allow it
+    pub async fn resolve_preferring(
+        config: &Config,
+        app: &AppExternal<'_>,
+        preferred: &[LockedPackage],
+    ) -> Result<Lock, LootError> {
+        let python_binary = app.get_python_binary(&config.python_version).context(|| {
+            LootError::interpreter(format!("Python {} is not installed", config.python_version))
+                .with_hint(format!(
+                    "Install it with loot install {}",
+                    config.python_version
+                ))
+        })?;
+        let environment = MarkerEnvironment::from_interpreter(&python_binary)?;
+
+        // Sections of lootbox.toml, named like the keys in the file
+        let all_requirements: Vec<(String, &HashMap<String, Dependency>)> =
+            std::iter::once(("requirements".to_owned(), &config.requirements))
+                .chain(
+                    config
+                        .groups
+                        .iter()
+                        .map(|(group, requirements)| (format!("groups.{}", group), requirements)),
+                )
+                .collect();
+
+        let mut requested = Vec::new();
+        let mut direct: HashMap<String, DirectPackage> = HashMap::new();
+        for (section, requirements) in all_requirements {
+            for project_requirement in project_requirements(&section, requirements, &environment)? {
+                let requirement = &project_requirement.requirement;
+                let dependency = requirements
+                    .iter()
+                    .find(|(name, _)| normalize_name(name) == requirement.name)
+                    .map(|(_, dependency)| dependency)
+                    .expect("Requirement comes from the config");
+
+                if let Some(source) = dependency.direct_source() {
+                    if !direct.contains_key(&requirement.name) {
+                        let package = fetch_direct_package(
+                            &requirement.name,
+                            source,
+                            app.data_path,
+                            &python_binary,
+                            &app.cache,
+                        )
+                        .await?;
+                        direct.insert(requirement.name.clone(), package);
+                    }
+                }
+                requested.push(project_requirement);
+            }
+        }
+
+        let packages = Resolver::new(config, app, &environment, &direct)?
+            .prefer(preferred)
+            .resolve(&requested)
+            .await?;
+
+        Ok(Lock {
+            python_version: config.python_version.clone(),
+            requirements: config.requirements.clone(),
+            groups: config.groups.clone(),
+            indexes: config.indexes.clone(),
+            resolution: config.resolution,
+            packages,
+        })
+    }
+}
+
+/// Requirements of a section of lootbox.toml that apply to the environment
+pub fn project_requirements(
+    section: &str,
+    requirements: &HashMap<String, Dependency>,
+    environment: &MarkerEnvironment,
+) -> Result<Vec<ProjectRequirement>, LootError> {
+    let mut project_requirements = Vec::new();
+    for (name, dependency) in requirements {
+        let entry = format!("{}.{}", section, name);
+        let requirement = Requirement::from_dependency(name, dependency).map_err(|error| {
+            LootError::config(error).with_hint(format!("Fix {} in lootbox.toml", entry))
+        })?;
+        if requirement.evaluate_markers(environment, &[]) {
+            project_requirements.push(ProjectRequirement { entry, requirement });
+        }
+    }
+    Ok(project_requirements)
+}
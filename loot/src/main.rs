@@ -2,38 +2,72 @@ use clap::{Parser, Subcommand};
 use directories::ProjectDirs;
 use dotenv::dotenv;
 use inline_colorization::*;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio;
-use tokio::io::AsyncBufReadExt;
 
 mod add;
 mod app;
+mod bundle;
+mod cache;
 mod commands;
+mod error;
+mod export;
+mod import;
 mod install;
+mod lock;
 mod new;
-mod python_dependency_resolver;
+mod outdated;
+mod remove;
+mod requirements;
+mod resolver;
 mod run;
+mod shutdown;
+mod sources;
+mod sync;
+mod tree;
+mod update;
 mod utils;
+mod verify;
 mod versions;
 
 use crate::install::install_python_version;
 use add::add_dependency;
-use app::AppExternal;
-use commands::execute_command;
+use app::{AppExternal, Bound, ErrorFormat};
+use bundle::bundle_project;
+use export::{export_dependencies, ExportFormat};
+use import::import_dependencies;
 use new::new_project;
-use run::run_app;
+use outdated::{outdated_dependencies, OutputFormat};
+use remove::remove_dependencies;
+use run::{exec_command, run_app};
+use sync::sync_dependencies;
+use tree::{print_tree, print_why};
+use update::{update_dependencies, upgrade_dependencies};
+use verify::verify_venv;
 
 const DEPENDENCIES_FILE: &str = "lootbox.toml";
+const LOCK_FILE: &str = "lootbox.lock";
 const PYTHON_INSTALLS_DIRECTORY: &str = "python_installs";
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
-    /// Turn debugging information on
-    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    /// Turn debugging information on, errors show everything that caused them
+    #[arg(short, long, global = true, action = clap::ArgAction::SetTrue)]
     debug: bool,
 
+    /// Use only the packages and metadata already in the cache, without network access
+    #[arg(long, global = true, action = clap::ArgAction::SetTrue)]
+    offline: bool,
+
+    /// Format of the errors meant to be read by tools, like dependency conflicts
+    #[arg(long, global = true, value_enum, default_value_t)]
+    error_format: ErrorFormat,
+
+    /// Seconds a running command gets to exit after Ctrl-C or SIGTERM before it is killed
+    #[arg(long, global = true, default_value_t = 10)]
+    grace_period: u64,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -66,19 +100,148 @@ enum Commands {
         #[arg(last = true)]
         args: Vec<String>,
     },
-    /// Adds a dependency for the current project
+    /// Adds dependencies to the current project and installs them
     Add {
-        /// Package to add
-        package: String,
+        /// Packages to add, as PEP 508 requirements like "uvicorn[standard]>=0.30" or with the version
+        /// requirement inline, package@">=1.0,<2"
+        #[arg(required = true)]
+        packages: Vec<String>,
 
-        /// Version to add
+        /// Version or PEP 440 version requirement to add, only with a single package
         #[arg(short, long)]
         version: Option<String>,
+
+        /// Group to add the dependency to, like dev or test
+        #[arg(short, long)]
+        group: Option<String>,
+
+        /// Name of an index declared in lootbox.toml to get the package from
+        #[arg(short, long)]
+        index: Option<String>,
+
+        /// Allow pre-releases and dev releases when no version is given
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        pre: bool,
+
+        /// Requirement to write for the version, instead of the `bound` of lootbox.toml
+        #[arg(short, long, value_enum)]
+        bound: Option<Bound>,
+
+        /// Sync the venv afterwards, removing the packages that are not in the lock like loot sync
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        sync: bool,
+    },
+    /// Adds the requirements of a requirements.txt, pyproject.toml or Pipfile to the project
+    Import {
+        /// File to import. pyproject.toml and Pipfile are recognized by their name, anything else is read as a
+        /// requirements file
+        file: std::path::PathBuf,
+
+        /// Group to add the main requirements of the file to
+        #[arg(short, long)]
+        group: Option<String>,
+    },
+    /// Removes dependencies from the current project
+    Remove {
+        /// Packages to remove
+        #[arg(required = true)]
+        packages: Vec<String>,
+
+        /// Group to remove the dependencies from
+        #[arg(short, long)]
+        group: Option<String>,
+    },
+    /// Makes the venv match the resolved dependencies exactly
+    Sync {
+        /// Only show what would change
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        dry_run: bool,
+
+        /// Exit with an error if the venv is not in sync
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        check: bool,
+
+        /// Only install these groups instead of all of them
+        #[arg(long)]
+        group: Vec<String>,
+
+        /// Do not install any group, only the main requirements
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        no_dev: bool,
+    },
+    /// Resolves the dependencies again to the newest versions their requirements allow
+    Update {
+        /// Only update these packages, every other one keeps its locked version
+        packages: Vec<String>,
+    },
+    /// Same as update. With --latest the requirements in lootbox.toml are moved to the newest releases first
+    Upgrade {
+        /// Only upgrade these packages
+        packages: Vec<String>,
+
+        /// Rewrite the version requirements to the newest release of each package
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        latest: bool,
+    },
+    /// Lists the packages that have newer versions
+    Outdated {
+        /// Check the packages installed in the venv instead of the lock
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        installed: bool,
+
+        /// Print a table or JSON for other tools
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+
+        /// Exit with an error if any package has a newer version the project can use, useful in CI
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        exit_code: bool,
+    },
+    /// Writes the locked dependencies as a requirements.txt, pyproject.toml or pylock.toml for other tools
+    Export {
+        #[arg(long, value_enum, default_value_t)]
+        format: ExportFormat,
+
+        /// Add the hashes of every file of the packages so pip checks what it downloads
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        hashes: bool,
+
+        /// Only export these groups instead of all of them
+        #[arg(long)]
+        group: Vec<String>,
+
+        /// Do not export any group, only the main requirements
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        no_dev: bool,
+
+        /// File to write to instead of stdout
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Checks that the files installed in the venv have not changed since they were installed
+    Verify,
+    /// Shows the dependency tree of the project
+    Tree {
+        /// Only show the tree of this package
+        package: Option<String>,
+
+        /// Levels of dependencies to show
+        #[arg(long)]
+        depth: Option<usize>,
+
+        /// Show what requires each package instead of what it requires
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        invert: bool,
+    },
+    /// Shows every path from the project requirements to a package
+    Why {
+        /// Package to explain
+        package: String,
     },
     /// Runs a command inside the venv. Usefull if using dependencies that have a Cli
     Exec {
-        /// Command to run
-        #[arg(allow_hyphen_values = true)]
+        /// Command to run, each argument is passed as it is without a shell
+        #[arg(required = true, allow_hyphen_values = true, trailing_var_arg = true)]
         command: Vec<String>,
     },
     /// Bundle the project into a version executable without lootbox
@@ -90,86 +253,22 @@ async fn main() {
     let cli = Cli::parse();
     dotenv().ok();
 
-    tokio::spawn(async move {
-        let _ = tokio::signal::ctrl_c().await;
-        std::process::exit(0);
-    });
+    tokio::spawn(shutdown::handle_signals(Duration::from_secs(
+        cli.grace_period,
+    )));
 
     let project_dirs =
         ProjectDirs::from("cli", "Asempere", "py-lootbox").expect("Project dir not found");
     let data_path = project_dirs.data_dir();
 
-    let (sender, mut receiver) = tokio::sync::mpsc::channel(5);
-    let (response_sender, response_receiver) = tokio::sync::mpsc::channel(1);
-    let commands_thread_handle = tokio::spawn(async move {
-        use std::io::Write;
-
-        #[cfg(target_os = "windows")]
-        let mut command = std::process::Command::new("powershell");
-        #[cfg(target_os = "windows")]
-        command.arg("-nologo");
-
-        #[cfg(not(target_os = "windows"))]
-        let mut command = std::process::Command::new("sh");
-
-        command
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped());
-        for (key, value) in std::env::vars() {
-            command.env(key, value);
-        }
-
-        let mut child = command.spawn().expect("Error creating child process");
-        let stdout = Arc::new(Mutex::new(
-            child.stdout.take().expect("Child does not have stdin"),
-        ));
-        let stderr = Arc::new(Mutex::new(
-            child.stderr.take().expect("Child does not have stderr"),
-        ));
-        let stdin = Arc::new(tokio::sync::Mutex::new(
-            child.stdin.take().expect("Child does not have stdin"),
-        ));
-
-        // Task to read stdin.
-        let stdin_clone = stdin.clone();
-        tokio::spawn(async move {
-            let mut own_stdin = tokio::io::BufReader::new(tokio::io::stdin()).lines();
-            let child_stdin = stdin_clone;
-            loop {
-                match own_stdin.next_line().await {
-                    Ok(Some(line)) => {
-                        let mut stdin = child_stdin.lock().await;
-                        let _ = writeln!(stdin, "{}", line);
-                    }
-                    Ok(None) => (),
-                    Err(error) => println!("Error reading stdin: {}", error),
-                }
-            }
-        });
-
-        while let Some(command) = receiver.recv().await {
-            execute_command(
-                command,
-                stdout.clone(),
-                stderr.clone(),
-                stdin.clone(),
-                &response_sender,
-            )
-            .await;
-        }
-
-        writeln!(stdin.lock().await, "exit").expect("Error writting to stdin");
-        let _ = child.wait();
-    });
-
-    let mut app = AppExternal::new(data_path, sender, response_receiver);
+    let app = AppExternal::new(data_path, cli.offline);
 
     if cli.debug {
         println!("{color_yellow}Debug mode is on{color_reset}");
     }
+    set_panic_hook(cli.debug);
 
-    match &cli.command {
+    let result = match &cli.command {
         Some(Commands::New {
             name,
             python_version,
@@ -182,41 +281,100 @@ async fn main() {
                     .to_string_lossy()
             );
 
-            new_project(name, python_version, force, app).await;
-        }
-        Some(Commands::Install { version, force }) => {
-            install_python_version(version, force, app).await;
+            new_project(name, python_version, force, app)
+                .await
+                .map(|()| 0)
         }
-        Some(Commands::Run { args }) => {
-            run_app(args, app).await;
+        Some(Commands::Install { version, force }) => install_python_version(version, force, app)
+            .await
+            .map(|()| 0),
+        Some(Commands::Run { args }) => run_app(args, app).await,
+        Some(Commands::Add {
+            packages,
+            version,
+            group,
+            index,
+            pre,
+            bound,
+            sync,
+        }) => add_dependency(packages, version, group, index, pre, bound, sync, app)
+            .await
+            .map(|()| 0),
+        Some(Commands::Import { file, group }) => {
+            import_dependencies(file, group, app).await.map(|()| 0)
         }
-        Some(Commands::Add { package, version }) => {
-            add_dependency(package, version, app).await;
-        }
-        Some(Commands::Exec { command }) => {
-            app.make_internal(None).await;
-            app.run_internal_command(command.join(" ")).await;
-            drop(app);
-        }
-        Some(Commands::Bundle) => {
-            println!("{color_yellow}Remember to run the project once at least before bundling to resolve its dependencies{color_reset}");
-            let _ = std::fs::remove_dir_all(&PathBuf::from("./target"));
-            crate::utils::clone_dir(&PathBuf::from("./src"), &PathBuf::from("./target"))
-                .expect("Error cloning souce code");
-            app.make_internal(None).await;
-            app.run_internal_command("pip freeze > ./target/requirements.txt".to_owned())
-                .await;
-            drop(app);
+        Some(Commands::Remove { packages, group }) => {
+            remove_dependencies(packages, group, app).await.map(|()| 0)
         }
+        Some(Commands::Sync {
+            dry_run,
+            check,
+            group,
+            no_dev,
+        }) => sync_dependencies(dry_run, check, group, no_dev, app).await,
+        Some(Commands::Update { packages }) => update_dependencies(packages, app).await.map(|()| 0),
+        Some(Commands::Upgrade { packages, latest }) => upgrade_dependencies(packages, latest, app)
+            .await
+            .map(|()| 0),
+        Some(Commands::Export {
+            format,
+            hashes,
+            group,
+            no_dev,
+            output,
+        }) => export_dependencies(format, hashes, group, no_dev, output, app)
+            .await
+            .map(|()| 0),
+        Some(Commands::Outdated {
+            installed,
+            format,
+            exit_code,
+        }) => outdated_dependencies(installed, format, exit_code, app).await,
+        Some(Commands::Verify) => verify_venv(app).await,
+        Some(Commands::Tree {
+            package,
+            depth,
+            invert,
+        }) => print_tree(package, depth, invert, app).await.map(|()| 0),
+        Some(Commands::Why { package }) => print_why(package, app).await.map(|()| 0),
+        Some(Commands::Exec { command }) => exec_command(command, app).await,
+        Some(Commands::Bundle) => bundle_project(app).await.map(|()| 0),
         None => {
-            drop(app);
             println!(
                 "py-lootbox {}, type 'loot help' for info",
                 env!("CARGO_PKG_VERSION")
-            )
+            );
+            Ok(0)
         }
     };
 
-    let _ = commands_thread_handle.await;
-    std::process::exit(0);
+    // Exit codes of programs and checks are passed on once everything the command made is dropped
+    match result {
+        Ok(0) => (),
+        Ok(code) => std::process::exit(code),
+        Err(error) => {
+            eprintln!("{}", error.render(cli.error_format, cli.debug));
+            std::process::exit(error.exit_code());
+        }
+    }
+}
+
+/// Failures that are not a `LootError` yet are shown as an error too instead of a panic, unless debugging
+fn set_panic_hook(debug: bool) {
+    let default_hook = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        if debug {
+            default_hook(info);
+        } else {
+            let message = info
+                .payload()
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| info.payload().downcast_ref::<&str>().copied())
+                .unwrap_or("Unexpected error");
+            eprintln!("{color_red}error: {}{color_reset}", message);
+        }
+        std::process::exit(1);
+    }));
 }
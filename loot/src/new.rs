@@ -1,6 +1,9 @@
 use std::fs;
 
 use crate::app::AppExternal;
+use crate::commands::args;
+use crate::error::{Context, LootError};
+use crate::shutdown::partial_dir;
 use crate::utils::create_file_with_content;
 use crate::DEPENDENCIES_FILE;
 
@@ -9,69 +12,100 @@ pub async fn new_project(
     python_version: &String,
     force: &bool,
     mut app: AppExternal<'_>,
-) {
+) -> Result<(), LootError> {
     // Check if dir is empty
     if name.exists() {
         if *force {
-            fs::remove_dir_all(name).expect("Error cleaning target dir");
+            fs::remove_dir_all(name)
+                .context(|| LootError::config(format!("Error cleaning {}", name.display())))?;
         } else {
-            panic!("Target directory is not empty. Use --force to override it");
+            return Err(
+                LootError::config(format!("{} already exists", name.display()))
+                    .with_hint("Use --force to replace it"),
+            );
         }
     }
 
     // Create all directories
-    fs::create_dir_all(name.join("src")).expect("Error creating src dir");
+    let _partial = partial_dir(name);
+    let project_file = |file: &str| {
+        let file = file.to_owned();
+        move || LootError::config(format!("Error creating {}", file))
+    };
+    fs::create_dir_all(name.join("src")).context(project_file("src"))?;
 
     // Create all files needed
     create_file_with_content(
         &name.join("src").join("main.py"),
         include_bytes!("default_files/default_main.py"),
     )
-    .expect("Error creating default main.py");
+    .context(project_file("src/main.py"))?;
     create_file_with_content(
         &name.join(DEPENDENCIES_FILE),
-        generate_default_requirements(&name.to_string_lossy(), &python_version).as_bytes(),
+        generate_default_requirements(&name.to_string_lossy(), python_version).as_bytes(),
     )
-    .expect("Error creating default lootbox project file");
+    .context(project_file(DEPENDENCIES_FILE))?;
 
-    create_lootbox_dir(Some(name), python_version, &mut app).await;
+    create_lootbox_dir(Some(name), python_version, &mut app).await
 }
 
 pub async fn create_lootbox_dir(
     path: Option<&std::path::PathBuf>,
     python_version: &String,
     app: &mut AppExternal<'_>,
-) {
+) -> Result<(), LootError> {
     let source_location = match path {
         Some(path) => path,
         None => &std::path::PathBuf::new(),
     };
 
     if !source_location.join(DEPENDENCIES_FILE).exists() {
-        panic!("Not inside a lootbox directory");
+        return Err(LootError::config(format!(
+            "There is no {} here",
+            DEPENDENCIES_FILE
+        )));
     }
     let location = source_location.join(".lootbox");
 
+    // Checked first, an empty .lootbox would be taken as a valid one
+    let python_binary = app.get_python_binary(python_version).context(|| {
+        LootError::interpreter(format!("Python {} is not installed", python_version))
+            .with_hint(format!("Install it with loot install {}", python_version))
+    })?;
+
     // Create all files
+    let _partial = partial_dir(&location);
     let _ = fs::remove_dir_all(&location);
-    fs::create_dir_all(&location).expect("Error creating .lootbox dir");
+    fs::create_dir_all(&location).context(|| LootError::venv("Error creating .lootbox dir"))?;
 
     // Setup venv
-    let python_binary = app
-        .get_python_binary(python_version)
-        .expect("Python version does not exist");
-
-    let create_venv_command = format!(
-        "{} -m venv {}",
-        python_binary.to_string_lossy(),
-        location.join("venv").to_string_lossy()
-    );
-    app.run_external_command(create_venv_command).await;
+    let create_venv_command = vec![
+        python_binary.to_string_lossy().to_string(),
+        "-m".to_owned(),
+        "venv".to_owned(),
+        location.join("venv").to_string_lossy().to_string(),
+    ];
+    let output = app.run_external_command(create_venv_command).await;
+    if !output.success() {
+        return Err(LootError::venv(format!(
+            "Error creating the venv, python exited with {}",
+            output.status
+        )));
+    }
 
-    app.make_internal(Some(source_location.to_owned())).await;
+    app.make_internal(Some(source_location.to_owned())).await?;
 
-    app.run_internal_command("python -m pip install --upgrade pip".to_owned())
+    if !app.cache.offline {
+        app.run_internal_command(args(&[
+            "python",
+            "-m",
+            "pip",
+            "install",
+            "--upgrade",
+            "pip",
+        ]))
         .await;
+    }
 
     // Populate files
     let name = &app.app_config.as_ref().expect("Not inside project").name;
@@ -84,7 +118,7 @@ pub async fn create_lootbox_dir(
         &location.join(DEPENDENCIES_FILE),
         generate_default_requirements(name, python_version).as_bytes(),
     )
-    .expect("Error creating default lootbox project file");
+    .context(|| LootError::venv("Error writing the config of .lootbox"))
 }
 
 fn generate_default_requirements(name: &str, python_version: &str) -> String {
@@ -0,0 +1,175 @@
+use inline_colorization::*;
+use pep440_rs::Version;
+use serde::Serialize;
+use std::str::FromStr;
+
+use crate::app::{AppExternal, Config};
+use crate::error::{LootError, CHECK_FAILED};
+use crate::lock::Lock;
+use crate::sources::install_target;
+use crate::sync::{list_installed_packages, BOOTSTRAP_PACKAGES};
+use crate::utils::{normalize_name, print_table};
+use crate::versions::{available_versions, get_index_files, newest_version, released_versions};
+
+/// How reports meant for people or for other tools are printed
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+#[derive(Serialize, Debug)]
+struct OutdatedPackage {
+    name: String,
+    current: String,
+    /// Newest version that supports the python version of the project
+    compatible: Option<String>,
+    /// Newest version, even if it needs another python version
+    latest: Option<String>,
+    /// Required in lootbox.toml instead of by another package
+    direct: bool,
+}
+
+impl OutdatedPackage {
+    fn is_newer(&self, version: &Option<String>) -> bool {
+        let current = Version::from_str(&self.current).ok();
+        let version = version
+            .as_ref()
+            .and_then(|version| Version::from_str(version).ok());
+
+        matches!((current, version), (Some(current), Some(version)) if version > current)
+    }
+}
+
+pub async fn outdated_dependencies(
+    installed: &bool,
+    format: &OutputFormat,
+    exit_code: &bool,
+    mut app: AppExternal<'_>,
+) -> Result<i32, LootError> {
+    app.make_internal(None).await?;
+
+    let config = app.app_config.clone().expect("Config file does not exist");
+    let packages = if *installed {
+        installed_packages(&config, &mut app).await?
+    } else {
+        let lock = match Lock::read(None)? {
+            Some(lock) if lock.matches(&config) => lock,
+            _ => Lock::resolve(&config, &app).await?,
+        };
+        lock.packages
+            .into_iter()
+            .map(|package| (package.name, package.version, package.source))
+            .collect()
+    };
+    let python_version =
+        Version::from_str(&config.python_version).expect("Error parsing python version");
+    let direct: Vec<String> = std::iter::once(&config.requirements)
+        .chain(config.groups.values())
+        .flat_map(|requirements| requirements.keys())
+        .map(|name| normalize_name(name))
+        .collect();
+
+    let mut outdated = Vec::new();
+    for (name, version, source) in packages {
+        // Git, path and url dependencies are not in any index
+        if install_target(&source).is_some() {
+            continue;
+        }
+
+        let files = get_index_files(&source, &name, &app.cache).await?;
+        let package = OutdatedPackage {
+            direct: direct.contains(&name),
+            name,
+            current: version,
+            compatible: newest_version(&available_versions(&files, &python_version)),
+            latest: newest_version(&released_versions(&files)),
+        };
+        if package.is_newer(&package.compatible) || package.is_newer(&package.latest) {
+            outdated.push(package);
+        }
+    }
+
+    match format {
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&outdated).expect("Error serializing outdated packages")
+        ),
+        OutputFormat::Table if outdated.is_empty() => {
+            println!("{color_green}Everything is up to date{color_reset}")
+        }
+        OutputFormat::Table => {
+            // Packages that can only move to a version for another python are not highlighted
+            let rows: Vec<(&str, Vec<String>)> = outdated
+                .iter()
+                .map(|package| {
+                    let color = if package.is_newer(&package.compatible) {
+                        color_yellow
+                    } else {
+                        ""
+                    };
+                    let kind = if package.direct {
+                        "direct"
+                    } else {
+                        "transitive"
+                    };
+                    (
+                        color,
+                        vec![
+                            package.name.clone(),
+                            package.current.clone(),
+                            package.compatible.clone().unwrap_or_else(|| "-".to_owned()),
+                            package.latest.clone().unwrap_or_else(|| "-".to_owned()),
+                            kind.to_owned(),
+                        ],
+                    )
+                })
+                .collect();
+            print_table(
+                &["Package", "Current", "Compatible", "Latest", "Type"],
+                &rows,
+            );
+        }
+    }
+
+    // Only versions the project can move to count as outdated
+    if *exit_code
+        && outdated
+            .iter()
+            .any(|package| package.is_newer(&package.compatible))
+    {
+        return Ok(CHECK_FAILED);
+    }
+    Ok(0)
+}
+
+/// Name, version and source of what pip has in the venv, which is what runs even if it was changed after loot sync.
+/// The source is the one it was installed from, or the index of the project for packages loot did not install
+async fn installed_packages(
+    config: &Config,
+    app: &mut AppExternal<'_>,
+) -> Result<Vec<(String, String, String)>, LootError> {
+    let installed_lock = Lock::read_installed(None)?;
+    let mut installed: Vec<(String, String)> = list_installed_packages(app)
+        .await?
+        .into_iter()
+        .filter(|(name, _)| !BOOTSTRAP_PACKAGES.contains(&name.as_str()))
+        .collect();
+    installed.sort();
+
+    installed
+        .into_iter()
+        .map(|(name, version)| {
+            let locked = installed_lock
+                .iter()
+                .flat_map(|lock| &lock.packages)
+                .find(|package| package.name == name);
+            let source = match locked {
+                Some(package) => package.source.clone(),
+                None => config.index_for(&name)?,
+            };
+            Ok((name, version, source))
+        })
+        .collect()
+}
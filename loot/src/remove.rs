@@ -0,0 +1,104 @@
+use inline_colorization::*;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::app::{AppExternal, Dependency};
+use crate::error::{Context, LootError};
+use crate::lock::Lock;
+use crate::run::{installed_dependencies, uninstall_packages};
+use crate::utils::{create_file_with_content, normalize_name};
+
+pub async fn remove_dependencies(
+    packages: &Vec<String>,
+    group: &Option<String>,
+    mut app: AppExternal<'_>,
+) -> Result<(), LootError> {
+    app.make_internal(None).await?;
+
+    let mut config = app.app_config.clone().expect("Config file does not exist");
+    for package in packages {
+        if remove_requirement(config.group_requirements_mut(group), package).is_none() {
+            return Err(LootError::config(format!(
+                "{} is not a dependency of this project",
+                package
+            )));
+        }
+    }
+
+    config
+        .groups
+        .retain(|_, requirements| !requirements.is_empty());
+
+    // The lock is resolved before lootbox.toml is touched, so both are written or neither is
+    println!("Resolving dependencies");
+    let preferred = Lock::read(None)?
+        .map(|lock| lock.packages)
+        .unwrap_or_default();
+    let lock = Lock::resolve_preferring(&config, &app, &preferred).await?;
+
+    create_file_with_content(
+        &PathBuf::from(crate::DEPENDENCIES_FILE),
+        toml::to_string_pretty(&config)
+            .expect("Could not convert to toml")
+            .as_bytes(),
+    )
+    .context(|| LootError::config("Error writing to lootbox.toml"))?;
+    lock.write(None)?;
+
+    // The venv reflects the old config, not the current one. Only the removed packages are
+    // dropped from it so other pending changes are still applied on the next run.
+    let mut old_config = AppExternal::get_old_config(None)?;
+    let old_lock = Lock::read_installed(None)?;
+    let installed_dependencies =
+        installed_dependencies(&old_config, old_lock.clone(), &app).await?;
+    for package in packages {
+        remove_requirement(old_config.group_requirements_mut(group), package);
+    }
+    old_config
+        .groups
+        .retain(|_, requirements| !requirements.is_empty());
+    let kept_dependencies = Lock::resolve(&old_config, &app).await?;
+
+    let kept_names: HashSet<_> = kept_dependencies
+        .packages
+        .iter()
+        .map(|package| &package.name)
+        .collect();
+    let orphaned: Vec<_> = installed_dependencies
+        .iter()
+        .map(|package| &package.name)
+        .filter(|name| !kept_names.contains(*name))
+        .collect();
+
+    for name in &orphaned {
+        println!("Removing {color_yellow}{}{color_reset}", name);
+    }
+
+    uninstall_packages(&app, orphaned.iter().copied()).await;
+
+    AppExternal::save_old_config(None, &old_config)?;
+    if let Some(mut old_lock) = old_lock {
+        old_lock
+            .packages
+            .retain(|package| !orphaned.contains(&&package.name));
+        old_lock.requirements = old_config.requirements;
+        old_lock.groups = old_config.groups;
+        old_lock.indexes = old_config.indexes;
+        old_lock.resolution = old_config.resolution;
+        old_lock.write_installed(None)?;
+    }
+    Ok(())
+}
+
+/// Removes a requirement however its name is written, `Foo_Bar` removes `foo-bar`. Returns the name it had in
+/// lootbox.toml and the requirement
+pub fn remove_requirement(
+    requirements: &mut HashMap<String, Dependency>,
+    package: &str,
+) -> Option<(String, Dependency)> {
+    let key = requirements
+        .keys()
+        .find(|name| normalize_name(name) == normalize_name(package))?
+        .clone();
+    requirements.remove_entry(&key)
+}
@@ -0,0 +1,436 @@
+use pep440_rs::{Operator, Version, VersionSpecifier, VersionSpecifiers};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::app::{Dependency, DetailedDependency};
+use crate::error::{Context, LootError};
+use crate::utils::normalize_name;
+use crate::versions::parse_requirement;
+
+/// A PEP 508 requirement, `name[extras] specifiers; markers`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Requirement {
+    pub name: String,
+    pub extras: Vec<String>,
+    pub specifiers: VersionSpecifiers,
+    pub marker: Option<MarkerTree>,
+}
+
+impl Requirement {
+    pub fn from_dependency(name: &str, dependency: &Dependency) -> Result<Requirement, String> {
+        let specifiers = parse_requirement(dependency.version())
+            .map_err(|error| format!("Invalid requirement for {}: {}", name, error))?;
+        let marker = match dependency.markers() {
+            Some(markers) => Some(MarkerTree::from_str(markers)?),
+            None => None,
+        };
+
+        Ok(Requirement {
+            name: normalize_name(name),
+            extras: dependency
+                .extras()
+                .iter()
+                .map(|extra| normalize_name(extra))
+                .collect(),
+            specifiers,
+            marker,
+        })
+    }
+
+    /// Wether the requirement applies to the environment. Extras are the ones requested for the package declaring it
+    pub fn evaluate_markers(&self, environment: &MarkerEnvironment, extras: &[String]) -> bool {
+        match &self.marker {
+            Some(marker) => marker.evaluate(environment, extras),
+            None => true,
+        }
+    }
+}
+
+impl FromStr for Requirement {
+    type Err = String;
+
+    fn from_str(requirement: &str) -> Result<Self, Self::Err> {
+        let (requirement, marker) = match requirement.split_once(';') {
+            Some((requirement, marker)) => (requirement, Some(MarkerTree::from_str(marker)?)),
+            None => (requirement, None),
+        };
+        let requirement = requirement.trim();
+
+        let name_end = requirement
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.'))
+            .unwrap_or(requirement.len());
+        if name_end == 0 {
+            return Err(format!("Requirement has no name: {}", requirement));
+        }
+        let name = normalize_name(&requirement[..name_end]);
+        let mut rest = requirement[name_end..].trim_start();
+
+        let mut extras = Vec::new();
+        if let Some(extras_start) = rest.strip_prefix('[') {
+            let (extras_list, after) = extras_start
+                .split_once(']')
+                .ok_or_else(|| format!("Unclosed extras in {}", requirement))?;
+            extras = extras_list
+                .split(',')
+                .map(normalize_name)
+                .filter(|extra| !extra.is_empty())
+                .collect();
+            rest = after.trim();
+        }
+
+        let rest = rest
+            .strip_prefix('(')
+            .and_then(|rest| rest.strip_suffix(')'))
+            .unwrap_or(rest);
+        let specifiers = parse_requirement(rest)
+            .map_err(|error| format!("Invalid requirement {}: {}", requirement, error))?;
+
+        Ok(Requirement {
+            name,
+            extras,
+            specifiers,
+            marker,
+        })
+    }
+}
+
+/// A PEP 508 requirement split into the fields of lootbox.toml, with the name as it was written
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RequirementEntry {
+    pub name: String,
+    /// `None` if any version is allowed
+    pub version: Option<String>,
+    pub extras: Vec<String>,
+    pub markers: Option<String>,
+}
+
+impl RequirementEntry {
+    pub fn parse(requirement: &str) -> Result<RequirementEntry, String> {
+        let parsed = Requirement::from_str(requirement)?;
+        let requirement = requirement.trim();
+        let name_end = requirement
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.'))
+            .unwrap_or(requirement.len());
+
+        // A single == is the same as a bare version in lootbox.toml
+        let specifiers: Vec<_> = parsed.specifiers.iter().collect();
+        let version = match specifiers.as_slice() {
+            [] => None,
+            [specifier] if matches!(specifier.operator(), Operator::Equal) => {
+                Some(specifier.version().to_string())
+            }
+            _ => Some(parsed.specifiers.to_string()),
+        };
+
+        Ok(RequirementEntry {
+            name: requirement[..name_end].to_owned(),
+            version,
+            extras: parsed.extras,
+            markers: requirement
+                .split_once(';')
+                .map(|(_, markers)| markers.trim().to_owned()),
+        })
+    }
+
+    pub fn into_dependency(self) -> Dependency {
+        let version = self.version.unwrap_or_else(|| "*".to_owned());
+        if self.extras.is_empty() && self.markers.is_none() {
+            return Dependency::Version(version);
+        }
+
+        Dependency::Detailed(DetailedDependency {
+            version: Some(version),
+            extras: self.extras,
+            markers: self.markers,
+            ..Default::default()
+        })
+    }
+}
+
+/// Values of the PEP 508 marker variables for a python interpreter
+#[derive(Debug, Deserialize, Clone)]
+pub struct MarkerEnvironment(HashMap<String, String>);
+
+impl MarkerEnvironment {
+    pub fn from_interpreter(
+        python_binary: &std::path::Path,
+    ) -> Result<MarkerEnvironment, LootError> {
+        let error = || {
+            LootError::interpreter(format!(
+                "Could not read the environment of {}",
+                python_binary.display()
+            ))
+        };
+        let output = std::process::Command::new(python_binary)
+            .args(["-c", include_str!("./marker_environment.py")])
+            .output()
+            .context(error)?;
+
+        serde_json::from_slice(&output.stdout).context(error)
+    }
+
+    fn get(&self, variable: &str) -> Option<&str> {
+        // Legacy names from PEP 345
+        let variable = match variable {
+            "os.name" => "os_name",
+            "sys.platform" => "sys_platform",
+            "platform.version" => "platform_version",
+            "platform.machine" => "platform_machine",
+            "platform.python_implementation" => "platform_python_implementation",
+            "python_implementation" => "platform_python_implementation",
+            other => other,
+        };
+
+        self.0.get(variable).map(|value| value.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MarkerTree {
+    And(Vec<MarkerTree>),
+    Or(Vec<MarkerTree>),
+    Expression {
+        left: MarkerValue,
+        operator: String,
+        right: MarkerValue,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MarkerValue {
+    Variable(String),
+    Literal(String),
+}
+
+impl MarkerTree {
+    pub fn evaluate(&self, environment: &MarkerEnvironment, extras: &[String]) -> bool {
+        match self {
+            MarkerTree::And(markers) => markers
+                .iter()
+                .all(|marker| marker.evaluate(environment, extras)),
+            MarkerTree::Or(markers) => markers
+                .iter()
+                .any(|marker| marker.evaluate(environment, extras)),
+            MarkerTree::Expression {
+                left,
+                operator,
+                right,
+            } => evaluate_expression(left, operator, right, environment, extras),
+        }
+    }
+}
+
+fn evaluate_expression(
+    left: &MarkerValue,
+    operator: &str,
+    right: &MarkerValue,
+    environment: &MarkerEnvironment,
+    extras: &[String],
+) -> bool {
+    // `extra` is not part of the environment, it depends on what was requested for the package
+    let extra = match (left, right) {
+        (MarkerValue::Variable(variable), MarkerValue::Literal(extra))
+        | (MarkerValue::Literal(extra), MarkerValue::Variable(variable))
+            if variable == "extra" =>
+        {
+            Some(normalize_name(extra))
+        }
+        _ => None,
+    };
+    if let Some(extra) = extra {
+        return match operator {
+            "==" => extras.contains(&extra),
+            "!=" => !extras.contains(&extra),
+            _ => false,
+        };
+    }
+
+    let resolve = |value: &MarkerValue| match value {
+        MarkerValue::Variable(variable) => environment.get(variable).unwrap_or("").to_owned(),
+        MarkerValue::Literal(literal) => literal.clone(),
+    };
+    let left = resolve(left);
+    let right = resolve(right);
+
+    match operator {
+        "in" => right.contains(&left),
+        "not in" => !right.contains(&left),
+        "===" => left == right,
+        _ => {
+            // Versions are compared following PEP 440, anything else as strings
+            let version_comparison = Version::from_str(&left).ok().and_then(|version| {
+                VersionSpecifier::from_str(&format!("{}{}", operator, right))
+                    .ok()
+                    .map(|specifier| specifier.contains(&version))
+            });
+
+            version_comparison.unwrap_or(match operator {
+                "==" => left == right,
+                "!=" => left != right,
+                "<" => left < right,
+                "<=" => left <= right,
+                ">" => left > right,
+                ">=" => left >= right,
+                _ => false,
+            })
+        }
+    }
+}
+
+impl FromStr for MarkerTree {
+    type Err = String;
+
+    fn from_str(marker: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize_marker(marker)?;
+        let mut position = 0;
+
+        let tree = parse_or(&tokens, &mut position)?;
+        if position != tokens.len() {
+            return Err(format!(
+                "Unexpected {:?} in marker {}",
+                tokens[position], marker
+            ));
+        }
+
+        Ok(tree)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum MarkerToken {
+    OpenParenthesis,
+    CloseParenthesis,
+    And,
+    Or,
+    Operator(String),
+    Variable(String),
+    Literal(String),
+}
+
+fn tokenize_marker(marker: &str) -> Result<Vec<MarkerToken>, String> {
+    let chars: Vec<char> = marker.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            tokens.push(MarkerToken::OpenParenthesis);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(MarkerToken::CloseParenthesis);
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|&other| other == c)
+                .ok_or_else(|| format!("Unclosed string in marker {}", marker))?;
+            tokens.push(MarkerToken::Literal(
+                chars[i + 1..i + 1 + end].iter().collect(),
+            ));
+            i += end + 2;
+        } else if "<>=!~".contains(c) {
+            let mut operator = String::new();
+            while i < chars.len() && "<>=!~".contains(chars[i]) {
+                operator.push(chars[i]);
+                i += 1;
+            }
+            tokens.push(MarkerToken::Operator(operator));
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let mut word = String::new();
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || "_.".contains(chars[i])) {
+                word.push(chars[i]);
+                i += 1;
+            }
+            let token = match word.as_str() {
+                "and" => MarkerToken::And,
+                "or" => MarkerToken::Or,
+                "in" => MarkerToken::Operator("in".to_owned()),
+                "not" => MarkerToken::Operator("not".to_owned()),
+                _ => MarkerToken::Variable(word),
+            };
+
+            // `not in` is a single operator
+            if token == MarkerToken::Operator("in".to_owned())
+                && tokens.last() == Some(&MarkerToken::Operator("not".to_owned()))
+            {
+                tokens.pop();
+                tokens.push(MarkerToken::Operator("not in".to_owned()));
+            } else {
+                tokens.push(token);
+            }
+        } else {
+            return Err(format!("Unexpected character {} in marker {}", c, marker));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_or(tokens: &[MarkerToken], position: &mut usize) -> Result<MarkerTree, String> {
+    let mut markers = vec![parse_and(tokens, position)?];
+    while tokens.get(*position) == Some(&MarkerToken::Or) {
+        *position += 1;
+        markers.push(parse_and(tokens, position)?);
+    }
+
+    Ok(if markers.len() == 1 {
+        markers.remove(0)
+    } else {
+        MarkerTree::Or(markers)
+    })
+}
+
+fn parse_and(tokens: &[MarkerToken], position: &mut usize) -> Result<MarkerTree, String> {
+    let mut markers = vec![parse_expression(tokens, position)?];
+    while tokens.get(*position) == Some(&MarkerToken::And) {
+        *position += 1;
+        markers.push(parse_expression(tokens, position)?);
+    }
+
+    Ok(if markers.len() == 1 {
+        markers.remove(0)
+    } else {
+        MarkerTree::And(markers)
+    })
+}
+
+fn parse_expression(tokens: &[MarkerToken], position: &mut usize) -> Result<MarkerTree, String> {
+    if tokens.get(*position) == Some(&MarkerToken::OpenParenthesis) {
+        *position += 1;
+        let tree = parse_or(tokens, position)?;
+        if tokens.get(*position) != Some(&MarkerToken::CloseParenthesis) {
+            return Err("Unclosed parenthesis in marker".to_owned());
+        }
+        *position += 1;
+        return Ok(tree);
+    }
+
+    let left = parse_value(tokens, position)?;
+    let operator = match tokens.get(*position) {
+        Some(MarkerToken::Operator(operator)) if operator != "not" => operator.clone(),
+        other => return Err(format!("Expected a marker operator, found {:?}", other)),
+    };
+    *position += 1;
+    let right = parse_value(tokens, position)?;
+
+    Ok(MarkerTree::Expression {
+        left,
+        operator,
+        right,
+    })
+}
+
+fn parse_value(tokens: &[MarkerToken], position: &mut usize) -> Result<MarkerValue, String> {
+    let value = match tokens.get(*position) {
+        Some(MarkerToken::Variable(variable)) => MarkerValue::Variable(variable.clone()),
+        Some(MarkerToken::Literal(literal)) => MarkerValue::Literal(literal.clone()),
+        other => return Err(format!("Expected a marker value, found {:?}", other)),
+    };
+    *position += 1;
+
+    Ok(value)
+}
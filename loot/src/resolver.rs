@@ -0,0 +1,1311 @@
+//! Version solving with the PubGrub algorithm, as described in
+//! https://github.com/dart-lang/pub/blob/master/doc/solver.md
+//!
+//! Every package has a finite list of candidate versions, the ones in its index that are not yanked and support the
+//! project's python version, so sets of versions are sets of positions in that list. Extras are virtual packages,
+//! `name[extra]`, depending on the exact same version of the package and on the requirements of the extra.
+
+use pep440_rs::{Operator, Version};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::app::{AppExternal, Config, ResolutionStrategy};
+use crate::cache::Cache;
+use crate::error::{Context, LootError};
+use crate::lock::{Artifact, LockedPackage};
+use crate::requirements::{MarkerEnvironment, Requirement};
+use crate::sources::{fetch_release_metadata, DirectPackage, DistributionMetadata};
+use crate::versions::{available_versions, get_index_files, IndexFile};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Package {
+    /// The project being resolved
+    Root,
+    Base(String),
+    Extra(String, String),
+}
+
+impl Package {
+    fn from_requirement(requirement: &Requirement) -> Vec<Package> {
+        std::iter::once(Package::Base(requirement.name.clone()))
+            .chain(
+                requirement
+                    .extras
+                    .iter()
+                    .map(|extra| Package::Extra(requirement.name.clone(), extra.clone())),
+            )
+            .collect()
+    }
+
+    fn name(&self) -> Option<&String> {
+        match self {
+            Package::Root => None,
+            Package::Base(name) | Package::Extra(name, _) => Some(name),
+        }
+    }
+}
+
+impl fmt::Display for Package {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Package::Root => write!(f, "the project"),
+            Package::Base(name) => write!(f, "{}", name),
+            Package::Extra(name, extra) => write!(f, "{}[{}]", name, extra),
+        }
+    }
+}
+
+/// Positions in the candidate versions of a package
+type VersionSet = BTreeSet<usize>;
+
+/// A statement about a package, either it is selected with a version in the set or it is not
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Positive(VersionSet),
+    /// The package is not selected or it is selected with a version outside the set
+    Negative(VersionSet),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Relation {
+    Satisfied,
+    Contradicted,
+    Inconclusive,
+}
+
+impl Term {
+    fn any() -> Term {
+        Term::Negative(VersionSet::new())
+    }
+
+    fn negate(&self) -> Term {
+        match self {
+            Term::Positive(set) => Term::Negative(set.clone()),
+            Term::Negative(set) => Term::Positive(set.clone()),
+        }
+    }
+
+    fn intersection(&self, other: &Term) -> Term {
+        match (self, other) {
+            (Term::Positive(a), Term::Positive(b)) => Term::Positive(a & b),
+            (Term::Positive(a), Term::Negative(b)) | (Term::Negative(b), Term::Positive(a)) => {
+                Term::Positive(a - b)
+            }
+            (Term::Negative(a), Term::Negative(b)) => Term::Negative(a | b),
+        }
+    }
+
+    fn union(&self, other: &Term) -> Term {
+        self.negate().intersection(&other.negate()).negate()
+    }
+
+    fn is_positive(&self) -> bool {
+        matches!(self, Term::Positive(_))
+    }
+
+    /// How this term relates to everything known about its package
+    fn relation_with(&self, known: &Term) -> Relation {
+        let intersection = self.intersection(known);
+        if &intersection == known {
+            Relation::Satisfied
+        } else if intersection == Term::Positive(VersionSet::new()) {
+            Relation::Contradicted
+        } else {
+            Relation::Inconclusive
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Cause {
+    /// The project has to be selected
+    NotRoot,
+    /// No candidate version is left for the package
+    NoVersions,
+    /// A version of a package depends on a requirement
+    Dependency {
+        dependency: Package,
+        /// Versions of the dependency that match
+        versions: VersionSet,
+        /// Extras depend on their own package without a requirement
+        requirement: Option<Box<Requirement>>,
+        /// Entry of lootbox.toml the requirement comes from, if it is a requirement of the project
+        entry: Option<String>,
+    },
+    /// Learned from two other incompatibilities
+    Derived(usize, usize),
+}
+
+/// Terms that can not all be true at once
+#[derive(Debug, Clone)]
+struct Incompatibility {
+    terms: BTreeMap<Package, Term>,
+    cause: Cause,
+}
+
+impl Incompatibility {
+    fn is_failure(&self) -> bool {
+        match self.terms.len() {
+            0 => true,
+            1 => {
+                matches!(self.terms.iter().next(), Some((Package::Root, term)) if term.is_positive())
+            }
+            _ => false,
+        }
+    }
+}
+
+enum IncompatibilityRelation {
+    Satisfied,
+    Contradicted,
+    AlmostSatisfied(Package),
+    Inconclusive,
+}
+
+#[derive(Debug, Clone)]
+struct Assignment {
+    package: Package,
+    term: Term,
+    decision_level: usize,
+    /// Incompatibility it was derived from, decisions have none
+    cause: Option<usize>,
+}
+
+/// What the lock needs to know about a resolved package, wherever it comes from
+pub struct PackageMetadata {
+    requires_dist: Vec<String>,
+    source: String,
+    artifacts: Vec<Artifact>,
+}
+
+impl PackageMetadata {
+    fn from_index(index: String, files: &[IndexFile], metadata: DistributionMetadata) -> Self {
+        PackageMetadata {
+            requires_dist: metadata.requires_dist,
+            source: index,
+            artifacts: files
+                .iter()
+                .filter_map(|file| {
+                    Some(Artifact {
+                        url: file.url.clone(),
+                        hash: format!("sha256:{}", file.sha256.as_ref()?),
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&DirectPackage> for PackageMetadata {
+    fn from(package: &DirectPackage) -> Self {
+        PackageMetadata {
+            requires_dist: package.metadata.requires_dist.clone(),
+            source: package.source.clone(),
+            artifacts: package.artifacts.clone(),
+        }
+    }
+}
+
+/// A requirement of the project and the entry of lootbox.toml it comes from, like `groups.dev.pytest`
+#[derive(Debug, Clone)]
+pub struct ProjectRequirement {
+    pub entry: String,
+    pub requirement: Requirement,
+}
+
+/// The requirements can not be satisfied, with an explanation of why
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolutionError {
+    /// Every step that led to the failure
+    pub report: String,
+    pub conflicts: Vec<Conflict>,
+    /// Entries of lootbox.toml involved in the failure
+    pub entries: Vec<ConflictEntry>,
+    /// Entries that could be relaxed or removed to solve it
+    pub suggestions: Vec<String>,
+}
+
+/// Two requirements on the same package no version satisfies at once, or one no version satisfies at all
+#[derive(Debug, Clone, Serialize)]
+pub struct Conflict {
+    pub package: String,
+    pub required_by: Requirer,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conflicts_with: Option<Requirer>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Requirer {
+    /// The project or the versions of a package
+    pub package: String,
+    pub requirement: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConflictEntry {
+    pub entry: String,
+    pub requirement: String,
+}
+
+impl fmt::Display for Requirer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} requires {}", self.package, self.requirement)?;
+        if let Some(entry) = &self.entry {
+            write!(f, " ({})", entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.conflicts_with {
+            Some(other) => write!(f, "{}, but {}", self.required_by, other),
+            None => write!(
+                f,
+                "{}, but no available version of {} matches it",
+                self.required_by, self.package
+            ),
+        }
+    }
+}
+
+impl From<ResolutionError> for LootError {
+    fn from(error: ResolutionError) -> LootError {
+        LootError::resolution(error.to_string().trim_end())
+            .with_details(serde_json::to_value(&error).expect("Error serializing resolution error"))
+    }
+}
+
+impl fmt::Display for ResolutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.report)?;
+
+        if !self.conflicts.is_empty() {
+            writeln!(f, "\nConflicts:")?;
+            for conflict in &self.conflicts {
+                writeln!(f, "  {}", conflict)?;
+            }
+        }
+        if !self.entries.is_empty() {
+            writeln!(f, "\nEntries of {} involved:", crate::DEPENDENCIES_FILE)?;
+            for entry in &self.entries {
+                writeln!(f, "  {} = {}", entry.entry, entry.requirement)?;
+            }
+        }
+        for suggestion in &self.suggestions {
+            write!(f, "\n{}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Resolver<'a> {
+    config: &'a Config,
+    cache: &'a Cache,
+    data_path: &'a Path,
+    python_binary: PathBuf,
+    python_version: Version,
+    environment: &'a MarkerEnvironment,
+    direct: &'a HashMap<String, DirectPackage>,
+
+    /// Candidate versions of every package seen, in ascending order
+    versions: HashMap<String, Vec<(Version, String)>>,
+    index_files: HashMap<String, Vec<IndexFile>>,
+    metadata: HashMap<(String, String), PackageMetadata>,
+    /// Versions of a previous lock that are kept whenever the requirements allow them
+    preferred: HashMap<String, String>,
+    /// Packages the project requires itself
+    direct_names: HashSet<String>,
+
+    incompatibilities: Vec<Incompatibility>,
+    package_incompatibilities: HashMap<Package, Vec<usize>>,
+    assignments: Vec<Assignment>,
+    decision_level: usize,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(
+        config: &'a Config,
+        app: &'a AppExternal<'_>,
+        environment: &'a MarkerEnvironment,
+        direct: &'a HashMap<String, DirectPackage>,
+    ) -> Result<Resolver<'a>, LootError> {
+        Ok(Resolver {
+            config,
+            cache: &app.cache,
+            data_path: app.data_path,
+            python_binary: app.get_python_binary(&config.python_version).context(|| {
+                LootError::interpreter(format!("Python {} is not installed", config.python_version))
+            })?,
+            python_version: Version::from_str(&config.python_version).context(|| {
+                LootError::config(format!(
+                    "Invalid python version {} in lootbox.toml",
+                    config.python_version
+                ))
+            })?,
+            environment,
+            direct,
+            versions: HashMap::new(),
+            index_files: HashMap::new(),
+            metadata: HashMap::new(),
+            incompatibilities: Vec::new(),
+            package_incompatibilities: HashMap::new(),
+            assignments: Vec::new(),
+            decision_level: 0,
+            preferred: HashMap::new(),
+            direct_names: HashSet::new(),
+        })
+    }
+
+    /// Keeps the packages at their locked version instead of the highest one when possible
+    pub fn prefer(mut self, packages: &[LockedPackage]) -> Resolver<'a> {
+        self.preferred = packages
+            .iter()
+            .map(|package| (package.name.clone(), package.version.clone()))
+            .collect();
+        self
+    }
+
+    /// Finds a version of every package needed by the requirements, which have to apply to the environment already.
+    /// Fails with a resolution error if they conflict, or with the error of an index that could not be read
+    pub async fn resolve(
+        mut self,
+        requirements: &[ProjectRequirement],
+    ) -> Result<Vec<LockedPackage>, LootError> {
+        self.direct_names = requirements
+            .iter()
+            .map(|project_requirement| project_requirement.requirement.name.clone())
+            .collect();
+        let mut root_terms = BTreeMap::new();
+        root_terms.insert(Package::Root, Term::Negative(VersionSet::from([0])));
+        self.add_incompatibility(Incompatibility {
+            terms: root_terms,
+            cause: Cause::NotRoot,
+        });
+
+        let mut next = Package::Root;
+        loop {
+            if let Err(failure) = self.propagate(next) {
+                return Err(self.error(failure).into());
+            }
+
+            match self.decide(requirements).await? {
+                Some(package) => next = package,
+                None => break,
+            }
+        }
+
+        Ok(self.solution())
+    }
+
+    fn candidates(&self, package: &Package) -> &[(Version, String)] {
+        match package.name() {
+            Some(name) => &self.versions[name],
+            None => ROOT_VERSION.as_slice(),
+        }
+    }
+
+    async fn fetch_versions(&mut self, name: &str) -> Result<(), LootError> {
+        if self.versions.contains_key(name) {
+            return Ok(());
+        }
+
+        let versions: Vec<String> = match self.direct.get(name) {
+            Some(package) => vec![package.metadata.version.clone()],
+            None => {
+                let index = self.config.index_for(name)?;
+                let files = get_index_files(&index, name, self.cache).await?;
+                let versions = available_versions(&files, &self.python_version);
+                self.index_files.insert(name.to_owned(), files);
+                versions
+            }
+        };
+
+        let mut versions: Vec<(Version, String)> = versions
+            .into_iter()
+            .filter_map(|version| Some((Version::from_str(&version).ok()?, version)))
+            .collect();
+        versions.sort_by(|a, b| a.0.cmp(&b.0));
+        self.versions.insert(name.to_owned(), versions);
+        Ok(())
+    }
+
+    async fn fetch_metadata(
+        &mut self,
+        name: &str,
+        version: &str,
+    ) -> Result<&PackageMetadata, LootError> {
+        let key = (name.to_owned(), version.to_owned());
+        if !self.metadata.contains_key(&key) {
+            let metadata = match self.direct.get(name) {
+                Some(package) => PackageMetadata::from(package),
+                None => {
+                    let index = self.config.index_for(name)?;
+                    let parsed = Version::from_str(version).ok();
+                    let files: Vec<IndexFile> = self.index_files[name]
+                        .iter()
+                        .filter(|file| {
+                            file.version()
+                                .and_then(|other| Version::from_str(&other).ok())
+                                == parsed
+                        })
+                        .cloned()
+                        .collect();
+                    let release = fetch_release_metadata(
+                        &files,
+                        self.data_path,
+                        &self.python_binary,
+                        self.cache,
+                    )
+                    .await?;
+
+                    PackageMetadata::from_index(index, &files, release)
+                }
+            };
+            self.metadata.insert(key.clone(), metadata);
+        }
+
+        Ok(&self.metadata[&key])
+    }
+
+    /// Requirements of a version of a package that apply to the environment, with their lootbox.toml entry if
+    /// they come from the project
+    async fn requirements_of(
+        &mut self,
+        package: &Package,
+        root_requirements: &[ProjectRequirement],
+        version: &str,
+    ) -> Result<Vec<(Option<String>, Requirement)>, LootError> {
+        let (name, extras) = match package {
+            Package::Root => {
+                return Ok(root_requirements
+                    .iter()
+                    .map(|root| (Some(root.entry.clone()), root.requirement.clone()))
+                    .collect())
+            }
+            Package::Base(name) => (name, Vec::new()),
+            Package::Extra(name, extra) => (name, vec![extra.clone()]),
+        };
+        let environment = self.environment;
+        let metadata = self.fetch_metadata(name, version).await?;
+
+        // A requirement that is skipped would leave packages out of the lock without anyone noticing
+        let requirements = metadata
+            .requires_dist
+            .iter()
+            .map(|requirement| {
+                Requirement::from_str(requirement).map_err(|error| {
+                    LootError::resolution(format!(
+                        "{} {} has an invalid requirement {}",
+                        name, version, requirement
+                    ))
+                    .caused_by(error)
+                })
+            })
+            .collect::<Result<Vec<Requirement>, LootError>>()?;
+        // Extras only add the requirements that are not there without them
+        Ok(requirements
+            .into_iter()
+            .filter(|requirement| {
+                requirement.evaluate_markers(environment, &extras)
+                    && (extras.is_empty() || !requirement.evaluate_markers(environment, &[]))
+            })
+            .map(|requirement| (None, requirement))
+            .collect())
+    }
+
+    fn add_incompatibility(&mut self, incompatibility: Incompatibility) -> usize {
+        let id = self.incompatibilities.len();
+        for package in incompatibility.terms.keys() {
+            self.package_incompatibilities
+                .entry(package.clone())
+                .or_default()
+                .push(id);
+        }
+        self.incompatibilities.push(incompatibility);
+        id
+    }
+
+    /// Everything the assignments say about a package
+    fn known_term(&self, package: &Package) -> Term {
+        self.assignments
+            .iter()
+            .filter(|assignment| &assignment.package == package)
+            .fold(Term::any(), |term, assignment| {
+                term.intersection(&assignment.term)
+            })
+    }
+
+    fn relation(&self, id: usize) -> IncompatibilityRelation {
+        let mut inconclusive = None;
+        for (package, term) in &self.incompatibilities[id].terms {
+            match term.relation_with(&self.known_term(package)) {
+                Relation::Satisfied => {}
+                Relation::Contradicted => return IncompatibilityRelation::Contradicted,
+                Relation::Inconclusive => {
+                    if inconclusive.is_some() {
+                        return IncompatibilityRelation::Inconclusive;
+                    }
+                    inconclusive = Some(package.clone());
+                }
+            }
+        }
+
+        match inconclusive {
+            Some(package) => IncompatibilityRelation::AlmostSatisfied(package),
+            None => IncompatibilityRelation::Satisfied,
+        }
+    }
+
+    fn derive(&mut self, package: Package, term: Term, cause: usize) {
+        self.assignments.push(Assignment {
+            package,
+            term,
+            decision_level: self.decision_level,
+            cause: Some(cause),
+        });
+    }
+
+    /// Derives everything the incompatibilities imply after a change to a package. Returns the incompatibility
+    /// that makes solving fail, if any
+    fn propagate(&mut self, package: Package) -> Result<(), usize> {
+        let mut changed = vec![package];
+
+        while let Some(package) = changed.pop() {
+            let ids = self
+                .package_incompatibilities
+                .get(&package)
+                .cloned()
+                .unwrap_or_default();
+
+            for id in ids.into_iter().rev() {
+                match self.relation(id) {
+                    IncompatibilityRelation::Satisfied => {
+                        let root_cause = self.resolve_conflict(id)?;
+
+                        // After backtracking only one term of the root cause is left undecided
+                        let IncompatibilityRelation::AlmostSatisfied(package) =
+                            self.relation(root_cause)
+                        else {
+                            panic!("Conflict resolution did not backtrack far enough");
+                        };
+                        let term = self.incompatibilities[root_cause].terms[&package].negate();
+                        self.derive(package.clone(), term, root_cause);
+                        changed = vec![package];
+                        break;
+                    }
+                    IncompatibilityRelation::AlmostSatisfied(package) => {
+                        let term = self.incompatibilities[id].terms[&package].negate();
+                        self.derive(package.clone(), term, id);
+                        if !changed.contains(&package) {
+                            changed.push(package);
+                        }
+                    }
+                    IncompatibilityRelation::Contradicted
+                    | IncompatibilityRelation::Inconclusive => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the cause of a satisfied incompatibility and backtracks until it is almost satisfied, learning
+    /// incompatibilities on the way. Returns the one to derive from, or the one that makes solving fail
+    fn resolve_conflict(&mut self, mut id: usize) -> Result<usize, usize> {
+        loop {
+            if self.incompatibilities[id].is_failure() {
+                return Err(id);
+            }
+
+            let (satisfier, previous_level) = self.find_satisfier(id);
+            let satisfier = self.assignments[satisfier].clone();
+
+            match satisfier.cause {
+                Some(cause) if previous_level >= satisfier.decision_level => {
+                    // The satisfier was derived at the same level, so its cause is part of the conflict
+                    let incompatibility = &self.incompatibilities[id];
+                    let satisfier_cause = &self.incompatibilities[cause];
+
+                    let mut terms = BTreeMap::new();
+                    for (package, term) in incompatibility
+                        .terms
+                        .iter()
+                        .chain(satisfier_cause.terms.iter())
+                    {
+                        if package == &satisfier.package {
+                            continue;
+                        }
+                        let term = match terms.get(package) {
+                            Some(other) => term.intersection(other),
+                            None => term.clone(),
+                        };
+                        terms.insert(package.clone(), term);
+                    }
+
+                    let term = incompatibility.terms[&satisfier.package]
+                        .union(&satisfier_cause.terms[&satisfier.package]);
+                    if term != Term::any() {
+                        terms.insert(satisfier.package.clone(), term);
+                    }
+
+                    id = self.add_incompatibility(Incompatibility {
+                        terms,
+                        cause: Cause::Derived(id, cause),
+                    });
+                }
+                _ => {
+                    self.backtrack(previous_level);
+                    return Ok(id);
+                }
+            }
+        }
+    }
+
+    /// Position of the earliest assignment that satisfies the incompatibility along with the ones before it, and
+    /// the decision level where the incompatibility is satisfied without the assignments between them
+    fn find_satisfier(&self, id: usize) -> (usize, usize) {
+        let terms = &self.incompatibilities[id].terms;
+        let satisfied = |known: &BTreeMap<&Package, Term>| {
+            terms
+                .iter()
+                .all(|(package, term)| term.relation_with(&known[package]) == Relation::Satisfied)
+        };
+
+        let mut known: BTreeMap<&Package, Term> =
+            terms.keys().map(|package| (package, Term::any())).collect();
+        let satisfier = self
+            .assignments
+            .iter()
+            .position(|assignment| {
+                if let Some(term) = known.get_mut(&assignment.package) {
+                    *term = term.intersection(&assignment.term);
+                }
+                satisfied(&known)
+            })
+            .expect("Incompatibility is not satisfied");
+        let satisfier_assignment = &self.assignments[satisfier];
+
+        let mut known: BTreeMap<&Package, Term> = terms
+            .keys()
+            .map(|package| {
+                if package == &satisfier_assignment.package {
+                    (package, satisfier_assignment.term.clone())
+                } else {
+                    (package, Term::any())
+                }
+            })
+            .collect();
+        let mut previous_level = 1;
+        if !satisfied(&known) {
+            for assignment in &self.assignments[..satisfier] {
+                if let Some(term) = known.get_mut(&assignment.package) {
+                    *term = term.intersection(&assignment.term);
+                }
+                if satisfied(&known) {
+                    previous_level = previous_level.max(assignment.decision_level);
+                    break;
+                }
+            }
+        }
+
+        (satisfier, previous_level)
+    }
+
+    fn backtrack(&mut self, decision_level: usize) {
+        self.assignments
+            .retain(|assignment| assignment.decision_level <= decision_level);
+        self.decision_level = decision_level;
+    }
+
+    fn decision(&self, package: &Package) -> Option<usize> {
+        self.assignments
+            .iter()
+            .find_map(|assignment| match assignment {
+                Assignment {
+                    package: other,
+                    cause: None,
+                    term: Term::Positive(set),
+                    ..
+                } if other == package => set.iter().next().copied(),
+                _ => None,
+            })
+    }
+
+    /// Picks a version for the next undecided package and adds its dependencies. Returns the package to propagate
+    /// from, or `None` if every package is decided
+    async fn decide(
+        &mut self,
+        root_requirements: &[ProjectRequirement],
+    ) -> Result<Option<Package>, LootError> {
+        // The package with the fewest versions left is the most likely to conflict
+        let mut undecided: Vec<(Package, VersionSet)> = Vec::new();
+        for assignment in &self.assignments {
+            if undecided
+                .iter()
+                .any(|(package, _)| package == &assignment.package)
+                || self.decision(&assignment.package).is_some()
+            {
+                continue;
+            }
+            if let Term::Positive(set) = self.known_term(&assignment.package) {
+                undecided.push((assignment.package.clone(), set));
+            }
+        }
+        let Some((package, set)) = undecided
+            .into_iter()
+            .min_by(|(a, a_set), (b, b_set)| a_set.len().cmp(&b_set.len()).then(a.cmp(b)))
+        else {
+            return Ok(None);
+        };
+
+        let Some(version) = self.choose_version(&package, &set) else {
+            let mut terms = BTreeMap::new();
+            terms.insert(package.clone(), Term::Positive(set));
+            self.add_incompatibility(Incompatibility {
+                terms,
+                cause: Cause::NoVersions,
+            });
+            return Ok(Some(package));
+        };
+
+        // Extras depend on their own package, besides the requirements they add
+        let version_string = self.candidates(&package)[version].1.clone();
+        let mut dependencies: Vec<Cause> = Vec::new();
+        if let Package::Extra(name, _) = &package {
+            dependencies.push(Cause::Dependency {
+                dependency: Package::Base(name.clone()),
+                versions: VersionSet::from([version]),
+                requirement: None,
+                entry: None,
+            });
+        }
+        for (entry, requirement) in self
+            .requirements_of(&package, root_requirements, &version_string)
+            .await?
+        {
+            self.fetch_versions(&requirement.name).await?;
+            for dependency in Package::from_requirement(&requirement) {
+                if dependency == package {
+                    continue;
+                }
+                let versions = self
+                    .candidates(&dependency)
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (version, _))| requirement.specifiers.contains(version))
+                    .map(|(position, _)| position)
+                    .collect();
+                dependencies.push(Cause::Dependency {
+                    dependency,
+                    versions,
+                    requirement: Some(Box::new(requirement.clone())),
+                    entry: entry.clone(),
+                });
+            }
+        }
+
+        let mut satisfied_dependency = false;
+        for cause in dependencies {
+            let Cause::Dependency {
+                dependency,
+                versions,
+                ..
+            } = &cause
+            else {
+                continue;
+            };
+            let mut terms = BTreeMap::new();
+            terms.insert(package.clone(), Term::Positive(VersionSet::from([version])));
+            // A requirement no version matches forbids the version on its own
+            if !versions.is_empty() {
+                terms.insert(dependency.clone(), Term::Negative(versions.clone()));
+            }
+
+            let id = self.add_incompatibility(Incompatibility { terms, cause });
+            satisfied_dependency |= self.incompatibilities[id]
+                .terms
+                .iter()
+                .filter(|(other, _)| other != &&package)
+                .all(|(other, term)| {
+                    term.relation_with(&self.known_term(other)) == Relation::Satisfied
+                });
+        }
+
+        // A version that would satisfy one of its incompatibilities is ruled out by propagation instead
+        if !satisfied_dependency {
+            self.decision_level += 1;
+            self.assignments.push(Assignment {
+                package: package.clone(),
+                term: Term::Positive(VersionSet::from([version])),
+                decision_level: self.decision_level,
+                cause: None,
+            });
+        }
+
+        Ok(Some(package))
+    }
+
+    /// The preferred version if it is in the set, otherwise the highest or lowest one depending on the strategy.
+    /// Pre-releases are only picked if there is nothing else
+    fn choose_version(&self, package: &Package, set: &VersionSet) -> Option<usize> {
+        let candidates = self.candidates(package);
+
+        let preferred = package.name().and_then(|name| self.preferred.get(name));
+        if let Some(position) = set
+            .iter()
+            .find(|position| Some(&candidates[**position].1) == preferred)
+        {
+            return Some(*position);
+        }
+
+        let lowest = match self.config.resolution {
+            ResolutionStrategy::Highest => false,
+            ResolutionStrategy::Lowest => true,
+            ResolutionStrategy::LowestDirect => package
+                .name()
+                .is_some_and(|name| self.direct_names.contains(name)),
+        };
+        let ordered: Vec<usize> = if lowest {
+            set.iter().copied().collect()
+        } else {
+            set.iter().rev().copied().collect()
+        };
+
+        ordered
+            .iter()
+            .find(|position| !candidates[**position].0.any_prerelease())
+            .or_else(|| ordered.first())
+            .copied()
+    }
+
+    fn solution(&self) -> Vec<LockedPackage> {
+        let mut selected: BTreeMap<&String, (String, Vec<String>)> = BTreeMap::new();
+        for assignment in &self.assignments {
+            let Some(name) = assignment.package.name() else {
+                continue;
+            };
+            let Some(version) = self.decision(&assignment.package) else {
+                continue;
+            };
+            let entry = selected
+                .entry(name)
+                .or_insert_with(|| (self.versions[name][version].1.clone(), Vec::new()));
+            if let Package::Extra(_, extra) = &assignment.package {
+                if !entry.1.contains(extra) {
+                    entry.1.push(extra.clone());
+                }
+            }
+        }
+
+        selected
+            .iter()
+            .map(|(name, (version, extras))| {
+                let metadata = &self.metadata[&((*name).clone(), version.clone())];
+
+                // Every requirement was parsed already when the version was decided
+                let mut dependencies: Vec<String> = metadata
+                    .requires_dist
+                    .iter()
+                    .filter_map(|requirement| Requirement::from_str(requirement).ok())
+                    .filter(|requirement| requirement.evaluate_markers(self.environment, extras))
+                    .map(|requirement| requirement.name)
+                    .filter(|dependency| dependency != *name && selected.contains_key(dependency))
+                    .collect();
+                dependencies.sort();
+                dependencies.dedup();
+
+                LockedPackage {
+                    name: (*name).clone(),
+                    version: version.clone(),
+                    source: metadata.source.clone(),
+                    dependencies,
+                    artifacts: metadata.artifacts.clone(),
+                }
+            })
+            .collect()
+    }
+
+    fn error(&self, failure: usize) -> ResolutionError {
+        let mut leaves = BTreeSet::new();
+        self.external_causes(failure, &mut leaves);
+
+        // Requirements that only differ in the versions of the package declaring them are merged
+        let mut requirers: Vec<(Package, VersionSet, &Package, &VersionSet, Requirer)> = Vec::new();
+        for id in leaves {
+            let incompatibility = &self.incompatibilities[id];
+            let Cause::Dependency {
+                dependency,
+                versions,
+                requirement,
+                entry,
+            } = &incompatibility.cause
+            else {
+                continue;
+            };
+            let Some((package, Term::Positive(package_versions))) = incompatibility
+                .terms
+                .iter()
+                .find(|(_, term)| term.is_positive())
+            else {
+                continue;
+            };
+            let requirement =
+                self.describe_dependency(dependency, versions, requirement.as_deref());
+
+            match requirers.iter_mut().find(|other| {
+                &other.0 == package
+                    && other.2 == dependency
+                    && other.4.requirement == requirement
+                    && &other.4.entry == entry
+            }) {
+                Some(other) => other.1.extend(package_versions.iter().copied()),
+                None => requirers.push((
+                    package.clone(),
+                    package_versions.clone(),
+                    dependency,
+                    versions,
+                    Requirer {
+                        package: String::new(),
+                        requirement,
+                        entry: entry.clone(),
+                    },
+                )),
+            }
+        }
+        for (package, versions, _, _, requirer) in &mut requirers {
+            requirer.package = self.describe_set(package, versions);
+        }
+
+        let mut conflicts = Vec::new();
+        for (position, (_, _, dependency, versions, requirer)) in requirers.iter().enumerate() {
+            if versions.is_empty() {
+                conflicts.push(Conflict {
+                    package: dependency.to_string(),
+                    required_by: requirer.clone(),
+                    conflicts_with: None,
+                });
+                continue;
+            }
+
+            for (_, _, other_dependency, other_versions, other) in &requirers[position + 1..] {
+                if other_dependency != dependency
+                    || other_versions.is_empty()
+                    || !versions.is_disjoint(other_versions)
+                {
+                    continue;
+                }
+                // Requirements of packages read better before the ones of the project
+                let (required_by, conflicts_with) = if requirer.entry.is_some() {
+                    (other.clone(), requirer.clone())
+                } else {
+                    (requirer.clone(), other.clone())
+                };
+                conflicts.push(Conflict {
+                    package: dependency.to_string(),
+                    required_by,
+                    conflicts_with: Some(conflicts_with),
+                });
+            }
+        }
+
+        let mut entries: Vec<ConflictEntry> = requirers
+            .iter()
+            .filter_map(|(_, _, _, _, requirer)| {
+                Some(ConflictEntry {
+                    entry: requirer.entry.clone()?,
+                    requirement: requirer.requirement.clone(),
+                })
+            })
+            .collect();
+        entries.sort_by(|a, b| a.entry.cmp(&b.entry));
+        entries.dedup();
+
+        ResolutionError {
+            report: self.report(failure),
+            conflicts,
+            suggestions: self.suggestions(&entries),
+            entries,
+        }
+    }
+
+    /// Incompatibilities that were not derived from others and led to the given one
+    fn external_causes(&self, id: usize, causes: &mut BTreeSet<usize>) {
+        match self.incompatibilities[id].cause {
+            Cause::Derived(first, second) => {
+                self.external_causes(first, causes);
+                self.external_causes(second, causes);
+            }
+            _ => {
+                causes.insert(id);
+            }
+        }
+    }
+
+    /// Entries to relax, pinned versions first since they are the most likely to be too strict
+    fn suggestions(&self, entries: &[ConflictEntry]) -> Vec<String> {
+        let requirements: Vec<(&String, &Requirement)> = entries
+            .iter()
+            .filter_map(|entry| {
+                self.incompatibilities.iter().find_map(|incompatibility| {
+                    match &incompatibility.cause {
+                        Cause::Dependency {
+                            requirement: Some(requirement),
+                            entry: Some(other),
+                            ..
+                        } if other == &entry.entry => Some((&entry.entry, requirement.as_ref())),
+                        _ => None,
+                    }
+                })
+            })
+            .collect();
+        let is_pin = |requirement: &Requirement| {
+            requirement.specifiers.iter().any(|specifier| {
+                matches!(specifier.operator(), Operator::Equal | Operator::ExactEqual)
+            })
+        };
+
+        let mut pinned: Vec<String> = requirements
+            .iter()
+            .filter(|(_, requirement)| is_pin(requirement))
+            .map(|(entry, _)| format!("Try relaxing {}, it pins a single version", entry))
+            .collect();
+        let constrained = requirements
+            .iter()
+            .filter(|(_, requirement)| {
+                !is_pin(requirement) && requirement.specifiers.iter().next().is_some()
+            })
+            .map(|(entry, _)| format!("Try relaxing {}", entry));
+        pinned.extend(constrained);
+
+        if pinned.is_empty() && !entries.is_empty() {
+            pinned.push(format!(
+                "None of the entries restricts versions, try removing one of {}",
+                entries
+                    .iter()
+                    .map(|entry| entry.entry.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ));
+        }
+        pinned
+    }
+
+    /// Explains why solving failed from the incompatibilities that led to it
+    fn report(&self, failure: usize) -> String {
+        let mut lines = Vec::new();
+
+        if let Cause::Derived(_, _) = self.incompatibilities[failure].cause {
+            self.explain(failure, &mut lines);
+        } else {
+            lines.push(format!(
+                "Because {}, version solving failed.",
+                self.describe(failure)
+            ));
+        }
+
+        lines.join("\n")
+    }
+
+    fn explain(&self, id: usize, lines: &mut Vec<String>) {
+        let Cause::Derived(first, second) = self.incompatibilities[id].cause else {
+            return;
+        };
+        let is_derived =
+            |id: usize| matches!(self.incompatibilities[id].cause, Cause::Derived(_, _));
+
+        match (is_derived(first), is_derived(second)) {
+            (false, false) => lines.push(format!(
+                "Because {} and {}, {}.",
+                self.describe(first),
+                self.describe(second),
+                self.describe(id)
+            )),
+            (true, false) | (false, true) => {
+                let (derived, external) = if is_derived(first) {
+                    (first, second)
+                } else {
+                    (second, first)
+                };
+                self.explain(derived, lines);
+                lines.push(format!(
+                    "And because {}, {}.",
+                    self.describe(external),
+                    self.describe(id)
+                ));
+            }
+            (true, true) => {
+                self.explain(first, lines);
+                self.explain(second, lines);
+                lines.push(format!(
+                    "So, because {} and {}, {}.",
+                    self.describe(first),
+                    self.describe(second),
+                    self.describe(id)
+                ));
+            }
+        }
+    }
+
+    /// A sentence stating what an incompatibility means
+    fn describe(&self, id: usize) -> String {
+        let incompatibility = &self.incompatibilities[id];
+        let terms: Vec<(&Package, &Term)> = incompatibility
+            .terms
+            .iter()
+            .filter(|(package, term)| !(package == &&Package::Root && term.is_positive()))
+            .collect();
+
+        match &incompatibility.cause {
+            Cause::NotRoot => return "the project has to be resolved".to_owned(),
+            Cause::NoVersions => {
+                if let Some((package, _)) = terms.first() {
+                    return format!("no version of {} matches every requirement on it", package);
+                }
+            }
+            Cause::Dependency {
+                dependency,
+                versions,
+                requirement,
+                ..
+            } => {
+                let (package, term) = incompatibility
+                    .terms
+                    .iter()
+                    .find(|(_, term)| term.is_positive())
+                    .expect("Dependencies have a positive term");
+                let subject = self.describe_term(package, term);
+                let requirement =
+                    self.describe_dependency(dependency, versions, requirement.as_deref());
+                return if incompatibility.terms.len() == 1 {
+                    format!(
+                        "{} depends on {}, which no available version matches",
+                        subject, requirement
+                    )
+                } else {
+                    format!("{} depends on {}", subject, requirement)
+                };
+            }
+            Cause::Derived(_, _) => {}
+        }
+
+        match terms.as_slice() {
+            [] => "version solving failed".to_owned(),
+            [(package, term)] if term.is_positive() => {
+                format!("{} is forbidden", self.describe_term(package, term))
+            }
+            [(package, term)] => {
+                format!(
+                    "{} is required",
+                    self.describe_term(package, &term.negate())
+                )
+            }
+            [(first, first_term), (second, second_term)]
+                if first_term.is_positive() != second_term.is_positive() =>
+            {
+                let (package, term, dependency, dependency_term) = if first_term.is_positive() {
+                    (first, first_term, second, second_term)
+                } else {
+                    (second, second_term, first, first_term)
+                };
+                format!(
+                    "{} depends on {}",
+                    self.describe_term(package, term),
+                    self.describe_term(dependency, &dependency_term.negate())
+                )
+            }
+            [(first, first_term), (second, second_term)] if first_term.is_positive() => {
+                format!(
+                    "{} is incompatible with {}",
+                    self.describe_term(first, first_term),
+                    self.describe_term(second, second_term)
+                )
+            }
+            _ => format!(
+                "{} are incompatible",
+                terms
+                    .iter()
+                    .map(|(package, term)| self.describe_term(package, term))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    fn describe_dependency(
+        &self,
+        dependency: &Package,
+        versions: &VersionSet,
+        requirement: Option<&Requirement>,
+    ) -> String {
+        match requirement {
+            Some(requirement) => describe_requirement(requirement),
+            None => self.describe_set(dependency, versions),
+        }
+    }
+
+    fn describe_term(&self, package: &Package, term: &Term) -> String {
+        match term {
+            Term::Positive(set) => self.describe_set(package, set),
+            Term::Negative(set) => format!("not {}", self.describe_set(package, set)),
+        }
+    }
+
+    /// Versions of a package as a range when they are contiguous, otherwise listed
+    fn describe_set(&self, package: &Package, set: &VersionSet) -> String {
+        if package == &Package::Root {
+            return package.to_string();
+        }
+
+        let candidates = self.candidates(package);
+        let (Some(&first), Some(&last)) = (set.iter().next(), set.iter().next_back()) else {
+            return format!("{} with no version", package);
+        };
+
+        if set.len() == 1 {
+            format!("{} {}", package, candidates[first].1)
+        } else if last - first + 1 == set.len() {
+            match (first == 0, last == candidates.len() - 1) {
+                (true, true) => package.to_string(),
+                (true, false) => format!("{} <={}", package, candidates[last].1),
+                (false, true) => format!("{} >={}", package, candidates[first].1),
+                (false, false) => format!(
+                    "{} >={}, <={}",
+                    package, candidates[first].1, candidates[last].1
+                ),
+            }
+        } else {
+            format!(
+                "{} {}",
+                package,
+                set.iter()
+                    .map(|position| candidates[*position].1.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            )
+        }
+    }
+}
+
+/// The project only has one version
+static ROOT_VERSION: std::sync::LazyLock<Vec<(Version, String)>> =
+    std::sync::LazyLock::new(|| vec![(Version::new([0]), "0".to_owned())]);
+
+fn describe_requirement(requirement: &Requirement) -> String {
+    let mut description = requirement.name.clone();
+    if !requirement.extras.is_empty() {
+        description.push_str(&format!("[{}]", requirement.extras.join(",")));
+    }
+    let specifiers = requirement.specifiers.to_string();
+    if !specifiers.is_empty() {
+        description.push(' ');
+        description.push_str(&specifiers);
+    }
+    description
+}
@@ -1,114 +1,266 @@
 use inline_colorization::*;
-use std::io::Write;
-use std::path::PathBuf;
-use std::{
-    collections::{HashMap, HashSet},
-    fs,
-};
+use std::path::{Path, PathBuf};
+use std::{collections::HashSet, fs};
 
 use crate::app::{AppExternal, Config};
-use crate::utils;
+use crate::cache::missing_offline;
+use crate::commands::args;
+use crate::error::{Context, LootError};
+use crate::lock::{Lock, LockedPackage};
+use crate::sources::install_target;
+use crate::utils::create_file_with_content;
+use crate::versions::PYPI_INDEX;
 
-pub async fn run_app(args: &Vec<String>, mut app: AppExternal<'_>) {
-    app.make_internal(None).await;
+/// Requirements files with the hashes each package is installed with, inside .lootbox
+const HASHES_DIRECTORY: &str = "hashes";
+
+/// Runs the project, returning the exit code of the program
+pub async fn run_app(args: &Vec<String>, mut app: AppExternal<'_>) -> Result<i32, LootError> {
+    app.make_internal(None).await?;
 
     let new_config = app.app_config.clone().expect("Config file not found");
-    let old_config = AppExternal::get_old_config(None);
+    let old_config = AppExternal::get_old_config(None)?;
+
+    let lock = Lock::get_or_resolve(None, &new_config, &app).await?;
+    let old_lock = Lock::read_installed(None)?;
 
-    if app.app_config.as_ref().unwrap() != &old_config {
-        handle_incorrect_config(&mut app, new_config, old_config).await;
+    if new_config != old_config || old_lock.as_ref() != Some(&lock) {
+        handle_incorrect_config(&mut app, new_config, old_config, lock, old_lock).await?;
     }
 
-    let command = format!("python ./src/main.py {}", args.join(" "));
-    app.run_internal_command(command).await;
+    let command = ["python", "./src/main.py"]
+        .into_iter()
+        .map(String::from)
+        .chain(args.iter().cloned())
+        .collect();
+    let output = app
+        .run_internal_command(command)
+        .await
+        .context(|| LootError::interpreter("Could not access python"))?;
+    Ok(output.exit_code())
+}
 
-    // otherwhise it is not guranteed that stoud is always flushed without any newline at the end, common if input() is used
-    tokio::spawn(async {
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-            let _ = std::io::stdout().flush();
-        }
-    });
+/// Runs a program of the venv, like a cli of a dependency, returning its exit code
+pub async fn exec_command(command: &[String], mut app: AppExternal<'_>) -> Result<i32, LootError> {
+    app.make_internal(None).await?;
+
+    let output = app
+        .run_internal_command(command.to_vec())
+        .await
+        .context(|| LootError::venv("The venv is not ready"))?;
+    Ok(output.exit_code())
 }
 
-async fn handle_incorrect_config(
+/// Brings the venv from the installed lock to `lock`, only touching the packages that changed
+pub async fn handle_incorrect_config(
     app: &mut AppExternal<'_>,
     new_config: Config,
-    mut old_config: Config,
-) {
-    if new_config.python_version != old_config.python_version {
-        println!("{color_yellow}Upgrading python version{color_reset}");
-        fs::remove_dir_all("./.lootbox").expect("Error cleaning lootbox dir");
-        crate::new::create_lootbox_dir(None, &new_config.python_version, app).await;
+    old_config: Config,
+    lock: Lock,
+    old_lock: Option<Lock>,
+) -> Result<(), LootError> {
+    let old_dependencies = if new_config.python_version != old_config.python_version {
+        recreate_lootbox_dir(app, &new_config.python_version).await?;
+
+        Vec::new()
+    } else {
+        installed_dependencies(&old_config, old_lock, app).await?
+    };
+
+    let key = |package: &LockedPackage| {
+        (
+            package.name.clone(),
+            package.version.clone(),
+            package.source.clone(),
+        )
+    };
+    let old_set: HashSet<_> = old_dependencies.iter().map(key).collect();
+    let new_set: HashSet<_> = lock.packages.iter().map(key).collect();
 
-        app.make_internal(None).await;
+    let old_not_in_new: Vec<_> = old_dependencies
+        .iter()
+        .filter(|package| !new_set.contains(&key(package)))
+        .collect();
+    let new_not_in_old: Vec<_> = lock
+        .packages
+        .iter()
+        .filter(|package| !old_set.contains(&key(package)))
+        .collect();
 
-        old_config.requirements = HashMap::new();
+    if !old_not_in_new.is_empty() || !new_not_in_old.is_empty() {
+        uninstall_packages(app, old_not_in_new.iter().map(|package| &package.name)).await;
+        install_packages(app, new_not_in_old.into_iter()).await?;
     }
 
-    if old_config.requirements != new_config.requirements {
-        println!("Resolving_dependencies");
-        let dependencies =
-            crate::python_dependency_resolver::resolve_dependencies(&new_config.requirements)
-                .expect("Error resolving dependencies");
+    AppExternal::save_old_config(None, &new_config)?;
+    lock.write_installed(None)?;
+    Ok(())
+}
 
-        let old_dependencies =
-            crate::python_dependency_resolver::resolve_dependencies(&old_config.requirements)
-                .expect("Error resolving dependencies");
+pub async fn recreate_lootbox_dir(
+    app: &mut AppExternal<'_>,
+    python_version: &String,
+) -> Result<(), LootError> {
+    println!("{color_yellow}Upgrading python version{color_reset}");
+    fs::remove_dir_all("./.lootbox").context(|| LootError::venv("Error cleaning lootbox dir"))?;
+    crate::new::create_lootbox_dir(None, python_version, app).await?;
 
-        let old_set: HashSet<_> = old_dependencies.iter().collect();
-        let new_set: HashSet<_> = dependencies.iter().collect();
+    app.make_internal(None).await
+}
 
-        let old_not_in_new: Vec<_> = old_set.difference(&new_set).collect();
-        let new_not_in_old: Vec<_> = new_set.difference(&old_set).collect();
+/// Packages currently in the venv. Venvs created before lock files existed fall back to resolving the old config
+pub async fn installed_dependencies(
+    old_config: &Config,
+    old_lock: Option<Lock>,
+    app: &AppExternal<'_>,
+) -> Result<Vec<LockedPackage>, LootError> {
+    match old_lock {
+        Some(old_lock) => Ok(old_lock.packages),
+        None => Ok(Lock::resolve(old_config, app).await?.packages),
+    }
+}
 
-        // Wait for all other venv tasks to finish before installing dependencies.
-        // With output blocks the thread untill the output is recieved. This happens once all previous commands are done.
-        // This is important since in some cases those commands might be recreating the venv and we cant run commands inside a venv that doesnt exist.
-        let python_version_command_output = app
-            .run_internal_command_with_output("python --version".to_owned())
-            .await
-            .expect("Could not access python");
-        println!("{}", python_version_command_output.stdout);
+pub async fn install_packages<'a>(
+    app: &AppExternal<'_>,
+    packages: impl Iterator<Item = &'a LockedPackage>,
+) -> Result<(), LootError> {
+    // Commands are made before running any so missing cache entries fail before the venv is touched
+    let commands = packages
+        .map(|package| Ok((package.name.clone(), install_commands(app, package)?)))
+        .collect::<Result<Vec<(String, Vec<Vec<String>>)>, LootError>>()?;
 
-        println!("{:?}", dependencies);
+    let mut handles = Vec::new();
+    for (name, commands_to_run) in commands {
+        let handle = app.run_paralel_internal_command(None, commands_to_run);
+        handles.push((name, handle.await));
+
+        println!("install sent");
+    }
 
-        let mut handles = Vec::new();
-        for (name, _) in old_not_in_new {
-            let command_to_run = format!("pip uninstall -y {}", name);
-            let handle = app.run_paralel_internal_command(None, command_to_run);
-            handles.push(handle.await);
+    println!("all installs sent");
 
-            println!("uninstalls sent");
+    // A download that does not match the hashes in the lock makes pip fail
+    let mut failed = Vec::new();
+    for (name, handle) in handles {
+        if !handle.await.expect("Error waiting for install") {
+            failed.push(name);
         }
+    }
+    if !failed.is_empty() {
+        return Err(
+            LootError::venv(format!("Error installing {}", failed.join(", ")))
+                .with_hint("The output of pip above says why"),
+        );
+    }
+    Ok(())
+}
 
-        println!("all uninstalls sent");
+/// Packages from indexes are installed from the wheel cache, building or downloading the wheel first if it is not
+/// there, so every venv after the first one installs them without network access. The commands run one after the other
+fn install_commands(
+    app: &AppExternal<'_>,
+    package: &LockedPackage,
+) -> Result<Vec<Vec<String>>, LootError> {
+    let cache = &app.cache;
 
-        for handle in handles {
-            while !handle.is_finished() {}
+    let mut install = args(&["pip", "install", "--upgrade", "--no-deps"]);
+    if let Some(target) = install_target(&package.source) {
+        if cache.offline {
+            install.push("--no-index".to_owned());
+        }
+        // Direct downloads are checked against the hash they were locked with
+        let hashes = match package.source.strip_prefix("url+") {
+            Some(url) => hashes_file(package, &format!("{} @ {}", package.name, url))?,
+            None => None,
+        };
+        match hashes {
+            Some(hashes) => install.extend(require_hashes(&hashes)),
+            None => install.extend(target),
         }
+        return Ok(vec![install]);
+    }
 
-        let mut handles = Vec::new();
-        for (name, version) in new_not_in_old {
-            let command_to_run = format!("pip install --upgrade --no-deps {}=={}", name, version);
-            let handle = app.run_paralel_internal_command(None, command_to_run);
-            handles.push(handle.await);
+    let wheels = cache.wheels_dir(&package.source);
+    let requirement = format!("{}=={}", package.name, package.version);
+    let hashes = hashes_file(package, &requirement)?;
+    // Cached wheels are checked again when installed, so one that changed since it was downloaded is refused. Wheels
+    // built from a source distribution have no hash in the lock to be checked against
+    let locked_wheels = package
+        .artifacts
+        .iter()
+        .any(|artifact| artifact.url.ends_with(".whl"));
+    install.extend(args(&["--no-index", "--find-links"]));
+    install.push(wheels.to_string_lossy().to_string());
+    match &hashes {
+        Some(hashes) if locked_wheels => install.extend(require_hashes(hashes)),
+        _ => install.push(requirement.clone()),
+    }
+    if cache.has_wheel(&package.source, &package.name, &package.version) {
+        return Ok(vec![install]);
+    }
+    if cache.offline {
+        return Err(missing_offline(&requirement));
+    }
 
-            println!("install sent");
-        }
+    let mut download = args(&["pip", "wheel", "--no-deps", "--wheel-dir"]);
+    download.push(wheels.to_string_lossy().to_string());
+    if package.source != PYPI_INDEX {
+        download.extend(["--index-url".to_owned(), package.source.clone()]);
+    }
+    match hashes {
+        Some(hashes) => download.extend(require_hashes(&hashes)),
+        None => download.push(requirement),
+    }
+    Ok(vec![download, install])
+}
 
-        println!("all installs sent");
+fn require_hashes(hashes: &Path) -> Vec<String> {
+    vec![
+        "--require-hashes".to_owned(),
+        "-r".to_owned(),
+        hashes.to_string_lossy().to_string(),
+    ]
+}
 
-        for handle in handles {
-            while !handle.is_finished() {}
-        }
+/// Requirements file with the hashes of the lock for a requirement of the package, so pip refuses any download that
+/// does not match. `None` if the lock has no hashes for it
+fn hashes_file(package: &LockedPackage, requirement: &str) -> Result<Option<PathBuf>, LootError> {
+    if package.artifacts.is_empty() {
+        return Ok(None);
     }
 
-    utils::create_file_with_content(
-        &PathBuf::from(format!("./.lootbox/{}", crate::DEPENDENCIES_FILE)),
-        toml::to_string(&new_config)
-            .expect("Error serializing new config")
-            .as_bytes(),
-    )
-    .expect("Error writing new config");
+    let directory = PathBuf::from(".lootbox").join(HASHES_DIRECTORY);
+    let error = || LootError::venv(format!("Error writing the hashes of {}", package.name));
+    fs::create_dir_all(&directory).context(error)?;
+    let file = directory.join(format!("{}.txt", package.name));
+    let hashes: String = package
+        .artifacts
+        .iter()
+        .map(|artifact| format!(" --hash={}", artifact.hash))
+        .collect();
+    create_file_with_content(&file, format!("{}{}\n", requirement, hashes).as_bytes())
+        .context(error)?;
+
+    Ok(Some(file))
+}
+
+pub async fn uninstall_packages<'a>(
+    app: &AppExternal<'_>,
+    names: impl Iterator<Item = &'a String>,
+) {
+    let mut handles = Vec::new();
+    for name in names {
+        let mut command_to_run = args(&["pip", "uninstall", "-y"]);
+        command_to_run.push(name.clone());
+        let handle = app.run_paralel_internal_command(None, vec![command_to_run]);
+        handles.push(handle.await);
+
+        println!("uninstalls sent");
+    }
+
+    println!("all uninstalls sent");
+
+    for handle in handles {
+        handle.await.expect("Error waiting for uninstall");
+    }
 }
@@ -0,0 +1,157 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::process;
+
+/// Processes started by lootbox that are still running, and wether they lead their own process group
+static CHILDREN: Mutex<Vec<(u32, bool)>> = Mutex::new(Vec::new());
+/// Directories that are being created, removed if lootbox is stopped before they are complete
+static PARTIAL_DIRS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+#[cfg(not(target_os = "windows"))]
+const SIGKILL: i32 = libc::SIGKILL;
+#[cfg(target_os = "windows")]
+const SIGKILL: i32 = 9;
+
+/// A running child process, forgotten once dropped
+pub struct ChildGuard(u32);
+
+/// Starts a process and tracks it while holding the lock the signal handler takes, so a signal can not arrive after
+/// it started but before it is tracked. `None` once lootbox is stopping, nothing starts then
+pub fn spawn_child(
+    command: &mut process::Command,
+    own_group: bool,
+) -> Option<std::io::Result<(process::Child, Option<ChildGuard>)>> {
+    let mut children = lock(&CHILDREN);
+    if is_stopping() {
+        return None;
+    }
+
+    let child = match command.spawn() {
+        Ok(child) => child,
+        Err(error) => return Some(Err(error)),
+    };
+    let guard = child.id().map(|pid| {
+        children.push((pid, own_group));
+        ChildGuard(pid)
+    });
+    Some(Ok((child, guard)))
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        lock(&CHILDREN).retain(|(pid, _)| *pid != self.0);
+    }
+}
+
+/// A directory that is removed if lootbox is stopped while creating it. Dropping the guard marks it as complete
+pub struct PartialDir(PathBuf);
+
+pub fn partial_dir(path: &Path) -> PartialDir {
+    lock(&PARTIAL_DIRS).push(path.to_owned());
+    PartialDir(path.to_owned())
+}
+
+impl Drop for PartialDir {
+    fn drop(&mut self) {
+        lock(&PARTIAL_DIRS).retain(|path| *path != self.0);
+    }
+}
+
+/// Lootbox got a signal and no new work should start
+pub fn is_stopping() -> bool {
+    STOPPING.load(Ordering::SeqCst)
+}
+
+/// Waits for SIGINT or SIGTERM. The children get the signal and the grace period to finish, then they are killed,
+/// the partial directories are removed and lootbox exits with 128 + the signal
+pub async fn handle_signals(grace_period: Duration) {
+    let signal = wait_for_signal().await;
+    {
+        let children = lock(&CHILDREN);
+        STOPPING.store(true, Ordering::SeqCst);
+        for (pid, own_group) in children.iter() {
+            forward_signal(*pid, *own_group, signal);
+        }
+    }
+
+    if !wait_for_children(grace_period).await {
+        for (pid, own_group) in lock(&CHILDREN).iter() {
+            kill(*pid, *own_group, SIGKILL);
+        }
+        // Killed children still have to be waited for, or they would outlive lootbox as zombies
+        wait_for_children(Duration::from_secs(1)).await;
+    }
+
+    for path in lock(&PARTIAL_DIRS).iter() {
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    std::process::exit(128 + signal);
+}
+
+/// Waits until every child has exited, or the timeout. Tells wether they all exited
+async fn wait_for_children(timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while !lock(&CHILDREN).is_empty() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    true
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(not(target_os = "windows"))]
+async fn wait_for_signal() -> i32 {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut interrupt = signal(SignalKind::interrupt()).expect("Error listening for SIGINT");
+    let mut terminate = signal(SignalKind::terminate()).expect("Error listening for SIGTERM");
+    tokio::select! {
+        _ = interrupt.recv() => libc::SIGINT,
+        _ = terminate.recv() => libc::SIGTERM,
+    }
+}
+
+#[cfg(target_os = "windows")]
+async fn wait_for_signal() -> i32 {
+    let _ = tokio::signal::ctrl_c().await;
+    2
+}
+
+/// Children in the process group of lootbox already got a Ctrl-C from the terminal
+#[cfg(not(target_os = "windows"))]
+fn forward_signal(pid: u32, own_group: bool, signal: i32) {
+    if own_group || signal != libc::SIGINT {
+        kill(pid, own_group, signal);
+    }
+}
+
+/// The console sends Ctrl-C to every process attached to it
+#[cfg(target_os = "windows")]
+fn forward_signal(_pid: u32, _own_group: bool, _signal: i32) {}
+
+#[cfg(not(target_os = "windows"))]
+fn kill(pid: u32, own_group: bool, signal: i32) {
+    let target = if own_group { -(pid as i32) } else { pid as i32 };
+    // SAFETY: kill has no memory safety requirements, a process that already exited just returns an error
+    unsafe {
+        libc::kill(target, signal);
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn kill(pid: u32, _own_group: bool, _signal: i32) {
+    let _ = std::process::Command::new("taskkill")
+        .args(["/F", "/T", "/PID", &pid.to_string()])
+        .output();
+}
@@ -0,0 +1,473 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::app::DetailedDependency;
+use crate::cache::{missing_offline, Cache};
+use crate::error::{Context, LootError};
+use crate::lock::Artifact;
+use crate::utils::short_hash;
+use crate::versions::IndexFile;
+
+const GIT_DIRECTORY: &str = "git";
+const BUILDS_DIRECTORY: &str = "builds";
+
+/// Metadata of a distribution, only the parts needed to resolve it
+#[derive(Debug, Clone)]
+pub struct DistributionMetadata {
+    pub name: String,
+    pub version: String,
+    pub requires_dist: Vec<String>,
+}
+
+/// A dependency installed from git, a local path or a direct URL instead of an index
+#[derive(Debug, Clone)]
+pub struct DirectPackage {
+    pub metadata: DistributionMetadata,
+    /// Where the package comes from, `git+{url}@{commit}`, `path+{path}`, `editable+{path}` or `url+{url}`
+    pub source: String,
+    pub artifacts: Vec<Artifact>,
+}
+
+pub async fn fetch_direct_package(
+    name: &str,
+    dependency: &DetailedDependency,
+    data_path: &Path,
+    python_binary: &Path,
+    cache: &Cache,
+) -> Result<DirectPackage, LootError> {
+    let package = if let Some(url) = &dependency.git {
+        let (checkout, commit) =
+            checkout_git(url, dependency.rev.as_deref(), data_path, cache.offline)?;
+
+        DirectPackage {
+            metadata: read_directory_metadata(&checkout, data_path, python_binary)?,
+            source: format!("git+{}@{}", url, commit),
+            artifacts: Vec::new(),
+        }
+    } else if let Some(path) = &dependency.path {
+        let directory = PathBuf::from(path);
+        if !directory.is_dir() {
+            return Err(LootError::config(format!(
+                "Path dependency {} does not exist",
+                path
+            )));
+        }
+
+        let kind = if dependency.editable {
+            "editable"
+        } else {
+            "path"
+        };
+        DirectPackage {
+            metadata: read_directory_metadata(&directory, data_path, python_binary)?,
+            source: format!("{}+{}", kind, path),
+            artifacts: Vec::new(),
+        }
+    } else if let Some(url) = &dependency.url {
+        let (file, hash) = download(url, cache).await?;
+
+        DirectPackage {
+            metadata: read_archive_metadata(&file, data_path, python_binary)?,
+            source: format!("url+{}", url),
+            artifacts: vec![Artifact {
+                url: url.clone(),
+                hash,
+            }],
+        }
+    } else {
+        return Err(LootError::config(format!(
+            "{} is not a git, path or url dependency",
+            name
+        )));
+    };
+
+    if crate::utils::normalize_name(&package.metadata.name) != crate::utils::normalize_name(name) {
+        return Err(LootError::config(format!(
+            "Dependency {} points to package {}",
+            name, package.metadata.name
+        )));
+    }
+
+    Ok(package)
+}
+
+/// Arguments pip has to be given to install a package from a source
+pub fn install_target(source: &str) -> Option<Vec<String>> {
+    if let Some(path) = source.strip_prefix("editable+") {
+        Some(vec!["-e".to_owned(), path.to_owned()])
+    } else if let Some(path) = source.strip_prefix("path+") {
+        Some(vec![path.to_owned()])
+    } else if source.starts_with("git+") {
+        Some(vec![source.to_owned()])
+    } else {
+        source.strip_prefix("url+").map(|url| vec![url.to_owned()])
+    }
+}
+
+/// Output of a git command, `None` if it failed
+fn git(args: &[&str], directory: Option<&Path>) -> Result<Option<String>, LootError> {
+    let mut command = std::process::Command::new("git");
+    if let Some(directory) = directory {
+        command.current_dir(directory);
+    }
+
+    let output = command.args(args).output().context(|| {
+        LootError::config("Could not run git").with_hint("Install git to use git dependencies")
+    })?;
+    if output.status.success() {
+        Ok(Some(
+            String::from_utf8_lossy(&output.stdout).trim().to_owned(),
+        ))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Clones or updates the repository in the data dir and checks out the revision. Returns the checkout and its commit.
+/// Offline the last fetched state of the repository is used
+fn checkout_git(
+    url: &str,
+    rev: Option<&str>,
+    data_path: &Path,
+    offline: bool,
+) -> Result<(PathBuf, String), LootError> {
+    let checkout = data_path.join(GIT_DIRECTORY).join(short_hash(url));
+
+    if offline {
+        if !checkout.exists() {
+            return Err(missing_offline(url));
+        }
+    } else if checkout.exists() {
+        git(&["fetch", "--quiet", "--tags", "origin"], Some(&checkout))?
+            .context(|| LootError::network(format!("Error fetching {}", url)))?;
+    } else {
+        fs::create_dir_all(data_path.join(GIT_DIRECTORY))
+            .context(|| LootError::config("Error creating the git dir in the data dir"))?;
+        git(
+            &["clone", "--quiet", url, &checkout.to_string_lossy()],
+            None,
+        )?
+        .context(|| LootError::network(format!("Error cloning {}", url)))?;
+    }
+
+    // Branches are taken from the remote so they are never stale
+    let candidates = match rev {
+        Some(rev) => vec![format!("origin/{}", rev), rev.to_owned()],
+        None => vec!["origin/HEAD".to_owned(), "HEAD".to_owned()],
+    };
+    let mut commit = None;
+    for candidate in &candidates {
+        commit = git(
+            &[
+                "rev-parse",
+                "--verify",
+                &format!("{}^{{commit}}", candidate),
+            ],
+            Some(&checkout),
+        )?;
+        if commit.is_some() {
+            break;
+        }
+    }
+    let commit = commit.context(|| {
+        LootError::config(format!(
+            "Revision {} not found in {}",
+            rev.unwrap_or("HEAD"),
+            url
+        ))
+    })?;
+
+    git(
+        &["checkout", "--quiet", "--detach", &commit],
+        Some(&checkout),
+    )?
+    .context(|| LootError::config(format!("Error checking out {} in {}", commit, url)))?;
+
+    Ok((checkout, commit))
+}
+
+/// Downloads a file to the cache, returning where it is and its hash. `file://` urls are read from disk
+async fn download(url: &str, cache: &Cache) -> Result<(PathBuf, String), LootError> {
+    let url_without_fragment = url.split('#').next().unwrap_or(url);
+
+    let (_, file) = cache
+        .fetch_file(url_without_fragment, "*/*")
+        .await?
+        .context(|| LootError::network(format!("Error downloading {}, it does not exist", url)))?;
+    let content =
+        fs::read(&file).context(|| LootError::network(format!("Error reading {}", url)))?;
+    let hash = format!("sha256:{}", hex::encode(Sha256::digest(&content)));
+
+    Ok((file, hash))
+}
+
+/// Metadata of a release in an index. It is read from the METADATA the index serves if possible, otherwise a
+/// distribution is downloaded, preferring wheels
+pub async fn fetch_release_metadata(
+    files: &[IndexFile],
+    data_path: &Path,
+    python_binary: &Path,
+    cache: &Cache,
+) -> Result<DistributionMetadata, LootError> {
+    if let Some(file) = files
+        .iter()
+        .find(|file| file.is_wheel() && file.core_metadata)
+    {
+        let metadata_url = format!(
+            "{}.metadata",
+            file.url.split('#').next().unwrap_or(&file.url)
+        );
+        if let Some((_, metadata)) = cache.fetch(&metadata_url, "*/*").await? {
+            return parse_core_metadata(&String::from_utf8_lossy(&metadata), &metadata_url);
+        }
+    }
+
+    let file = files
+        .iter()
+        .find(|file| file.is_wheel())
+        .or_else(|| files.first())
+        .context(|| LootError::resolution("The release has no files to read its metadata from"))?;
+    let (file, _) = download(&file.url, cache).await?;
+
+    read_archive_metadata(&file, data_path, python_binary)
+}
+
+fn read_archive_metadata(
+    file: &Path,
+    data_path: &Path,
+    python_binary: &Path,
+) -> Result<DistributionMetadata, LootError> {
+    let file_name = file.to_string_lossy();
+
+    if file_name.ends_with(".whl") {
+        return read_wheel_metadata(file);
+    }
+
+    let invalid = || LootError::network(format!("{} is not a valid archive", file_name));
+    let extracted = data_path
+        .join(BUILDS_DIRECTORY)
+        .join(format!("{}.extracted", short_hash(&file_name)));
+    let _ = fs::remove_dir_all(&extracted);
+    let archive = fs::File::open(file).context(invalid)?;
+    if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+        tar::Archive::new(flate2::read::GzDecoder::new(archive))
+            .unpack(&extracted)
+            .context(invalid)?;
+    } else if file_name.ends_with(".zip") {
+        zip::ZipArchive::new(archive)
+            .and_then(|mut zip| zip.extract(&extracted))
+            .context(invalid)?;
+    } else {
+        return Err(
+            LootError::resolution(format!("Unsupported archive {}", file_name))
+                .with_hint("Only wheels, .tar.gz and .zip source distributions can be installed"),
+        );
+    }
+
+    // Source distributions have everything inside a single `{name}-{version}` dir
+    let mut entries: Vec<PathBuf> = fs::read_dir(&extracted)
+        .context(invalid)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    let root = if entries.len() == 1 && entries[0].is_dir() {
+        entries.remove(0)
+    } else {
+        extracted
+    };
+
+    read_directory_metadata(&root, data_path, python_binary)
+}
+
+fn read_wheel_metadata(wheel: &Path) -> Result<DistributionMetadata, LootError> {
+    let invalid = || LootError::network(format!("{} is not a valid wheel", wheel.display()));
+    let mut archive =
+        zip::ZipArchive::new(fs::File::open(wheel).context(invalid)?).context(invalid)?;
+
+    let metadata_file = archive
+        .file_names()
+        .find(|file_name| {
+            file_name.ends_with(".dist-info/METADATA") && file_name.matches('/').count() == 1
+        })
+        .map(|file_name| file_name.to_owned())
+        .context(invalid)?;
+
+    let mut metadata = String::new();
+    archive
+        .by_name(&metadata_file)
+        .context(invalid)?
+        .read_to_string(&mut metadata)
+        .context(invalid)?;
+
+    parse_core_metadata(&metadata, &wheel.to_string_lossy())
+}
+
+/// Reads the metadata of a project directory. Static metadata is used if possible, otherwise a wheel is built
+fn read_directory_metadata(
+    directory: &Path,
+    data_path: &Path,
+    python_binary: &Path,
+) -> Result<DistributionMetadata, LootError> {
+    if let Some(metadata) = read_pyproject_metadata(directory) {
+        return Ok(metadata);
+    }
+
+    // Before metadata 2.2 a PKG-INFO can leave out requirements that setup.py only knows when it runs
+    let pkg_info = directory.join("PKG-INFO");
+    if let Ok(metadata) = fs::read_to_string(&pkg_info) {
+        if has_static_requirements(&metadata) {
+            return parse_core_metadata(&metadata, &pkg_info.to_string_lossy());
+        }
+    }
+
+    let wheel_directory = data_path
+        .join(BUILDS_DIRECTORY)
+        .join(short_hash(&directory.to_string_lossy()));
+    let _ = fs::remove_dir_all(&wheel_directory);
+
+    let build_error = || {
+        LootError::resolution(format!(
+            "Error building {} to read its metadata",
+            directory.display()
+        ))
+    };
+    let status = std::process::Command::new(python_binary)
+        .args(["-m", "pip", "wheel", "--no-deps", "--quiet", "--wheel-dir"])
+        .arg(&wheel_directory)
+        .arg(directory)
+        .status()
+        .context(|| LootError::interpreter(format!("Could not run {}", python_binary.display())))?;
+    if !status.success() {
+        return Err(build_error().with_hint("The output of pip above says why"));
+    }
+
+    let wheel = fs::read_dir(&wheel_directory)
+        .context(build_error)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| path.extension().is_some_and(|extension| extension == "whl"))
+        .context(build_error)?;
+
+    read_wheel_metadata(&wheel)
+}
+
+/// PEP 621 metadata from pyproject.toml. `None` if it is missing or dynamic
+fn read_pyproject_metadata(directory: &Path) -> Option<DistributionMetadata> {
+    let pyproject = fs::read_to_string(directory.join("pyproject.toml")).ok()?;
+    let pyproject: toml::Table = toml::from_str(&pyproject).ok()?;
+    let project = pyproject.get("project")?.as_table()?;
+
+    let dynamic: Vec<&str> = project
+        .get("dynamic")
+        .and_then(|dynamic| dynamic.as_array())
+        .map(|dynamic| dynamic.iter().filter_map(|field| field.as_str()).collect())
+        .unwrap_or_default();
+    if dynamic
+        .iter()
+        .any(|field| ["version", "dependencies", "optional-dependencies"].contains(field))
+    {
+        return None;
+    }
+
+    let mut requires_dist: Vec<String> = project
+        .get("dependencies")
+        .and_then(|dependencies| dependencies.as_array())
+        .map(|dependencies| {
+            dependencies
+                .iter()
+                .filter_map(|dependency| {
+                    dependency.as_str().map(|dependency| dependency.to_owned())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let optional_dependencies = project
+        .get("optional-dependencies")
+        .and_then(|optional| optional.as_table());
+    for (extra, dependencies) in optional_dependencies.into_iter().flatten() {
+        for dependency in dependencies.as_array().into_iter().flatten() {
+            if let Some(dependency) = dependency.as_str() {
+                requires_dist.push(match dependency.split_once(';') {
+                    Some((dependency, marker)) => {
+                        format!(
+                            "{}; ({}) and extra == \"{}\"",
+                            dependency,
+                            marker.trim(),
+                            extra
+                        )
+                    }
+                    None => format!("{}; extra == \"{}\"", dependency, extra),
+                });
+            }
+        }
+    }
+
+    Some(DistributionMetadata {
+        name: project.get("name")?.as_str()?.to_owned(),
+        version: project.get("version")?.as_str()?.to_owned(),
+        requires_dist,
+    })
+}
+
+/// Wether the Requires-Dist of a PKG-INFO are the ones of the built package, which is only promised from metadata
+/// version 2.2 on when they are not marked as dynamic
+fn has_static_requirements(metadata: &str) -> bool {
+    let headers = core_metadata_headers(metadata);
+    let version = headers
+        .iter()
+        .find(|(header, _)| header == "metadata-version")
+        .and_then(|(_, version)| {
+            let (major, minor) = version.split_once('.')?;
+            Some((major.parse::<u64>().ok()?, minor.parse::<u64>().ok()?))
+        });
+
+    version.is_some_and(|version| version >= (2, 2))
+        && !headers.iter().any(|(header, value)| {
+            header == "dynamic" && value.eq_ignore_ascii_case("requires-dist")
+        })
+}
+
+/// Headers of the email format of METADATA and PKG-INFO files, with their names in lowercase
+fn core_metadata_headers(metadata: &str) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+
+    for line in metadata.lines() {
+        // Headers end at the first empty line, the description comes after
+        if line.is_empty() {
+            break;
+        }
+
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_ascii_lowercase(), value.trim().to_owned()));
+        }
+    }
+    headers
+}
+
+/// Parses a METADATA or PKG-INFO file, read from `origin`
+fn parse_core_metadata(metadata: &str, origin: &str) -> Result<DistributionMetadata, LootError> {
+    let headers = core_metadata_headers(metadata);
+    let header = |key: &str| {
+        headers
+            .iter()
+            .find(|(header, _)| header == key)
+            .map(|(_, value)| value.clone())
+            .context(|| LootError::resolution(format!("The metadata in {} has no {}", origin, key)))
+    };
+
+    Ok(DistributionMetadata {
+        name: header("name")?,
+        version: header("version")?,
+        requires_dist: headers
+            .iter()
+            .filter(|(header, _)| header == "requires-dist")
+            .map(|(_, value)| value.clone())
+            .collect(),
+    })
+}
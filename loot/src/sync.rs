@@ -0,0 +1,166 @@
+use inline_colorization::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::app::AppExternal;
+use crate::commands::args;
+use crate::error::{Context, LootError, CHECK_FAILED};
+use crate::lock::Lock;
+use crate::run::{install_packages, recreate_lootbox_dir, uninstall_packages};
+use crate::utils::normalize_name;
+
+/// Packages the venv needs to work, they are never removed when syncing
+pub const BOOTSTRAP_PACKAGES: [&str; 3] = ["pip", "setuptools", "wheel"];
+
+#[derive(Deserialize, Debug)]
+struct InstalledPackage {
+    name: String,
+    version: String,
+}
+
+pub async fn sync_dependencies(
+    dry_run: &bool,
+    check: &bool,
+    groups: &[String],
+    no_dev: &bool,
+    mut app: AppExternal<'_>,
+) -> Result<i32, LootError> {
+    app.make_internal(None).await?;
+
+    let config = app.app_config.clone().expect("Config file not found");
+    let old_config = AppExternal::get_old_config(None)?;
+    let only_report = *dry_run || *check;
+
+    let (lock, lock_is_current) = match Lock::read(None)? {
+        Some(lock) if lock.matches(&config) => (lock, true),
+        Some(lock) => (
+            Lock::resolve_preferring(&config, &app, &lock.packages).await?,
+            false,
+        ),
+        None => (Lock::resolve(&config, &app).await?, false),
+    };
+    if !lock_is_current {
+        if only_report {
+            println!(
+                "{color_yellow}{} is out of date{color_reset}",
+                crate::LOCK_FILE
+            );
+        } else {
+            lock.write(None)?;
+        }
+    }
+
+    let python_changed = config.python_version != old_config.python_version;
+    let installed = if python_changed {
+        println!(
+            "Venv uses python {}, project uses python {}",
+            old_config.python_version, config.python_version
+        );
+        if !only_report {
+            recreate_lootbox_dir(&mut app, &config.python_version).await?;
+        }
+        HashMap::new()
+    } else {
+        list_installed_packages(&mut app).await?
+    };
+
+    // Every group is installed unless some are chosen explicitly
+    let groups: Vec<String> = if *no_dev || !groups.is_empty() {
+        groups.to_vec()
+    } else {
+        config.groups.keys().cloned().collect()
+    };
+    let packages = lock.packages_for_groups(&groups)?;
+
+    let mut to_install = Vec::new();
+    let mut to_upgrade = Vec::new();
+    for package in &packages {
+        match installed.get(&package.name) {
+            None => to_install.push(*package),
+            Some(version) if version != &package.version => {
+                println!(
+                    "  {color_yellow}~ {} {} -> {}{color_reset}",
+                    package.name, version, package.version
+                );
+                to_upgrade.push(*package);
+            }
+            Some(_) => (),
+        }
+    }
+    for package in &to_install {
+        println!(
+            "  {color_green}+ {} {}{color_reset}",
+            package.name, package.version
+        );
+    }
+
+    let mut to_remove: Vec<_> = installed
+        .keys()
+        .filter(|name| !BOOTSTRAP_PACKAGES.contains(&name.as_str()))
+        .filter(|name| !packages.iter().any(|package| &&package.name == name))
+        .cloned()
+        .collect();
+    to_remove.sort();
+    for name in &to_remove {
+        println!("  {color_red}- {} {}{color_reset}", name, installed[name]);
+    }
+
+    let venv_in_sync =
+        !python_changed && to_install.is_empty() && to_upgrade.is_empty() && to_remove.is_empty();
+    if venv_in_sync {
+        println!("{color_green}Venv is up to date{color_reset}");
+    }
+
+    if *check {
+        if !venv_in_sync || !lock_is_current {
+            return Ok(CHECK_FAILED);
+        }
+        return Ok(0);
+    }
+    if *dry_run {
+        return Ok(0);
+    }
+
+    uninstall_packages(&app, to_remove.iter()).await;
+    install_packages(&app, to_install.into_iter().chain(to_upgrade)).await?;
+
+    AppExternal::save_old_config(None, &config)?;
+    let installed_lock = Lock {
+        packages: packages.into_iter().cloned().collect(),
+        ..lock.clone()
+    };
+    installed_lock.write_installed(None)?;
+    Ok(0)
+}
+
+/// Packages installed in the venv, by normalized name
+pub async fn list_installed_packages(
+    app: &mut AppExternal<'_>,
+) -> Result<HashMap<String, String>, LootError> {
+    let output = app
+        .run_internal_command_with_output(args(&[
+            "python",
+            "-m",
+            "pip",
+            "list",
+            "--format=json",
+            "--disable-pip-version-check",
+        ]))
+        .await
+        .context(|| LootError::venv("Could not access python in the venv"))?;
+    if !output.success() {
+        return Err(LootError::venv(format!(
+            "Error listing installed packages: {}",
+            output.stderr.trim()
+        ))
+        .with_hint("Delete .lootbox and run loot sync to make the venv again"));
+    }
+
+    let packages: Vec<InstalledPackage> = serde_json::from_str(output.stdout.trim())
+        .context(|| LootError::venv("Error reading the installed packages"))?;
+
+    Ok(packages
+        .into_iter()
+        .map(|package| (normalize_name(&package.name), package.version))
+        .collect())
+}
@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::app::{AppExternal, Config};
+use crate::error::LootError;
+use crate::lock::{Lock, LockedPackage};
+use crate::utils::normalize_name;
+
+/// Dependency graph of a lock. The project and each of its groups are nodes too, depending on their requirements
+struct Graph<'a> {
+    packages: BTreeMap<&'a str, &'a LockedPackage>,
+    edges: BTreeMap<String, Vec<String>>,
+    /// The project and its groups
+    roots: Vec<String>,
+}
+
+impl<'a> Graph<'a> {
+    fn new(config: &Config, lock: &'a Lock) -> Graph<'a> {
+        let packages: BTreeMap<&str, &LockedPackage> = lock
+            .packages
+            .iter()
+            .map(|package| (package.name.as_str(), package))
+            .collect();
+
+        let mut groups: Vec<_> = config.groups.iter().collect();
+        groups.sort_by(|a, b| a.0.cmp(b.0));
+        let sections = std::iter::once((config.name.clone(), &config.requirements)).chain(
+            groups.into_iter().map(|(group, requirements)| {
+                (format!("{} [{}]", config.name, group), requirements)
+            }),
+        );
+
+        let mut edges = BTreeMap::new();
+        let mut roots = Vec::new();
+        for (root, requirements) in sections {
+            // Requirements whose markers do not apply are not locked
+            let mut names: Vec<String> = requirements
+                .keys()
+                .map(|name| normalize_name(name))
+                .filter(|name| packages.contains_key(name.as_str()))
+                .collect();
+            names.sort();
+            edges.insert(root.clone(), names);
+            roots.push(root);
+        }
+        for package in &lock.packages {
+            edges.insert(package.name.clone(), package.dependencies.clone());
+        }
+
+        Graph {
+            packages,
+            edges,
+            roots,
+        }
+    }
+
+    /// Same graph with every edge reversed, so each package points to what requires it
+    fn invert(self) -> Graph<'a> {
+        let mut edges: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (node, dependencies) in &self.edges {
+            edges.entry(node.clone()).or_default();
+            for dependency in dependencies {
+                edges
+                    .entry(dependency.clone())
+                    .or_default()
+                    .push(node.clone());
+            }
+        }
+
+        Graph { edges, ..self }
+    }
+
+    fn label(&self, node: &str) -> String {
+        match self.packages.get(node) {
+            Some(package) => format!("{} {}", package.name, package.version),
+            None => node.to_owned(),
+        }
+    }
+
+    fn children(&self, node: &str) -> &[String] {
+        self.edges
+            .get(node)
+            .map(|edges| edges.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn print(&self, root: &str, depth: Option<usize>) {
+        println!("{}", self.label(root));
+        self.print_children(root, "", 0, depth, &mut HashSet::new());
+    }
+
+    /// Packages whose children were already printed are marked with (*) instead of repeating them
+    fn print_children(
+        &self,
+        node: &str,
+        prefix: &str,
+        level: usize,
+        depth: Option<usize>,
+        expanded: &mut HashSet<String>,
+    ) {
+        if depth.is_some_and(|depth| level >= depth) {
+            return;
+        }
+        expanded.insert(node.to_owned());
+
+        let children = self.children(node);
+        for (position, child) in children.iter().enumerate() {
+            let last = position == children.len() - 1;
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+
+            let repeated = expanded.contains(child) && !self.children(child).is_empty();
+            println!(
+                "{}{}{}{}",
+                prefix,
+                branch,
+                self.label(child),
+                if repeated { " (*)" } else { "" }
+            );
+            if !repeated {
+                self.print_children(
+                    child,
+                    &format!("{}{}", prefix, indent),
+                    level + 1,
+                    depth,
+                    expanded,
+                );
+            }
+        }
+    }
+
+    /// Every path from a root to the node, without going through a node twice
+    fn paths_to(&self, node: &str) -> Vec<Vec<String>> {
+        let mut paths = Vec::new();
+        for root in &self.roots {
+            self.find_paths(node, &mut vec![root.clone()], &mut paths);
+        }
+        paths
+    }
+
+    fn find_paths(&self, target: &str, path: &mut Vec<String>, paths: &mut Vec<Vec<String>>) {
+        let node = path.last().expect("Paths start at a root").clone();
+        if node == target {
+            paths.push(path.clone());
+            return;
+        }
+
+        for child in self.children(&node) {
+            if path.contains(child) {
+                continue;
+            }
+            path.push(child.clone());
+            self.find_paths(target, path, paths);
+            path.pop();
+        }
+    }
+}
+
+async fn read_lock(app: &mut AppExternal<'_>) -> Result<(Config, Lock), LootError> {
+    app.make_internal(None).await?;
+
+    let config = app.app_config.clone().expect("Config file does not exist");
+    let lock = Lock::get_or_resolve(None, &config, app).await?;
+    Ok((config, lock))
+}
+
+fn locked_name(lock: &Lock, package: &str) -> Result<String, LootError> {
+    let name = normalize_name(package);
+    if !lock.packages.iter().any(|locked| locked.name == name) {
+        return Err(LootError::config(format!(
+            "{} is not in {}",
+            package,
+            crate::LOCK_FILE
+        )));
+    }
+    Ok(name)
+}
+
+pub async fn print_tree(
+    package: &Option<String>,
+    depth: &Option<usize>,
+    invert: &bool,
+    mut app: AppExternal<'_>,
+) -> Result<(), LootError> {
+    let (config, lock) = read_lock(&mut app).await?;
+    let graph = Graph::new(&config, &lock);
+
+    let roots: Vec<String> = match package {
+        Some(package) => vec![locked_name(&lock, package)?],
+        // Inverted trees start at the packages that do not depend on anything
+        None if *invert => lock
+            .packages
+            .iter()
+            .filter(|package| package.dependencies.is_empty())
+            .map(|package| package.name.clone())
+            .collect(),
+        None => graph.roots.clone(),
+    };
+    let graph = if *invert { graph.invert() } else { graph };
+
+    for root in roots {
+        graph.print(&root, *depth);
+    }
+    Ok(())
+}
+
+pub async fn print_why(package: &str, mut app: AppExternal<'_>) -> Result<(), LootError> {
+    let (config, lock) = read_lock(&mut app).await?;
+    let graph = Graph::new(&config, &lock);
+    let name = locked_name(&lock, package)?;
+
+    for path in graph.paths_to(&name) {
+        let labels: Vec<String> = path.iter().map(|node| graph.label(node)).collect();
+        println!("{}", labels.join(" -> "));
+    }
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use inline_colorization::*;
+use pep440_rs::{Operator, Version};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::app::{AppExternal, Bound, Config};
+use crate::error::{Context, LootError};
+use crate::lock::{Lock, LockedPackage};
+use crate::utils::{create_file_with_content, normalize_name, print_table};
+use crate::versions::{
+    bounded_requirement, get_versions_of_package, newest_version, parse_requirement,
+};
+
+pub async fn update_dependencies(
+    packages: &[String],
+    mut app: AppExternal<'_>,
+) -> Result<(), LootError> {
+    app.make_internal(None).await?;
+
+    let config = app.app_config.clone().expect("Config file does not exist");
+    let names: Vec<String> = packages.iter().map(|name| normalize_name(name)).collect();
+    let locked = Lock::read(None)?
+        .map(|lock| lock.packages)
+        .unwrap_or_default();
+    for name in &names {
+        if !locked.iter().any(|package| &package.name == name) {
+            return Err(LootError::config(format!(
+                "{} is not in {}",
+                name,
+                crate::LOCK_FILE
+            )));
+        }
+    }
+
+    update_lock(&config, &names, false, &app).await
+}
+
+/// Same as update, but with `latest` the requirements are first rewritten to the newest release of each package
+pub async fn upgrade_dependencies(
+    packages: &[String],
+    latest: &bool,
+    mut app: AppExternal<'_>,
+) -> Result<(), LootError> {
+    if !*latest {
+        return update_dependencies(packages, app).await;
+    }
+
+    app.make_internal(None).await?;
+
+    let mut config = app.app_config.clone().expect("Config file does not exist");
+    let names: Vec<String> = packages.iter().map(|name| normalize_name(name)).collect();
+
+    let mut targets: Vec<(Option<String>, String)> = config
+        .requirements
+        .keys()
+        .map(|name| (None, name.clone()))
+        .chain(config.groups.iter().flat_map(|(group, requirements)| {
+            requirements
+                .keys()
+                .map(|name| (Some(group.clone()), name.clone()))
+        }))
+        .filter(|(_, name)| names.is_empty() || names.contains(&normalize_name(name)))
+        .collect();
+    targets.sort();
+    for name in &names {
+        if !targets
+            .iter()
+            .any(|(_, other)| &normalize_name(other) == name)
+        {
+            return Err(LootError::config(format!(
+                "{} is not a dependency of this project",
+                name
+            )));
+        }
+    }
+
+    for (group, name) in targets {
+        let dependency = config.group_requirements_mut(&group)[&name].clone();
+        // Git, path and url dependencies have no releases to move to
+        if dependency.direct_source().is_some() {
+            continue;
+        }
+
+        let index = config.index_for(&name)?;
+        let versions = get_versions_of_package(&index, &name, &app.cache).await?;
+        let latest = newest_version(&versions)
+            .context(|| LootError::resolution(format!("{} has no versions", name)))?;
+
+        let Some(requirement) = pin_to_latest(dependency.version(), &latest) else {
+            if !allows_version(dependency.version(), &latest) {
+                println!(
+                    "{color_yellow}{} = \"{}\" is kept, change it by hand to allow {}{color_reset}",
+                    name,
+                    dependency.version(),
+                    latest
+                );
+            }
+            continue;
+        };
+        if requirement == dependency.version() {
+            continue;
+        }
+        println!(
+            "{} = \"{}\" -> \"{}\"",
+            name,
+            dependency.version(),
+            requirement
+        );
+        config
+            .group_requirements_mut(&group)
+            .insert(name, dependency.with_version(requirement));
+    }
+
+    update_lock(&config, &names, true, &app).await
+}
+
+/// Resolves the config again, keeping the packages that are not being updated at their locked version. With
+/// `write_config` lootbox.toml is written too, only once the resolution succeeded
+async fn update_lock(
+    config: &Config,
+    names: &[String],
+    write_config: bool,
+    app: &AppExternal<'_>,
+) -> Result<(), LootError> {
+    let locked = Lock::read(None)?
+        .map(|lock| lock.packages)
+        .unwrap_or_default();
+
+    // Everything is updated unless some packages are named
+    let preferred: Vec<LockedPackage> = if names.is_empty() {
+        Vec::new()
+    } else {
+        locked
+            .iter()
+            .filter(|package| !names.contains(&package.name))
+            .cloned()
+            .collect()
+    };
+
+    println!("Resolving dependencies");
+    let lock = Lock::resolve_preferring(config, app, &preferred).await?;
+    if write_config {
+        create_file_with_content(
+            &PathBuf::from(crate::DEPENDENCIES_FILE),
+            toml::to_string_pretty(config)
+                .expect("Could not convert to toml")
+                .as_bytes(),
+        )
+        .context(|| LootError::config("Error writing to lootbox.toml"))?;
+    }
+    lock.write(None)?;
+
+    print_version_changes(&locked, &lock.packages);
+    Ok(())
+}
+
+/// Requirement that allows the latest version, keeping the operator of a single specifier or a major bound. `None`
+/// if any version is allowed already, or for requirements it does not know how to move like `<2` or `>=1,!=1.4`
+fn pin_to_latest(requirement: &str, latest: &str) -> Option<String> {
+    let specifiers = parse_requirement(requirement).ok()?;
+    let specifiers: Vec<_> = specifiers.iter().collect();
+
+    match specifiers.as_slice() {
+        [] => None,
+        _ if Version::from_str(requirement.trim()).is_ok() => Some(latest.to_owned()),
+        [specifier]
+            if matches!(
+                specifier.operator(),
+                Operator::Equal
+                    | Operator::ExactEqual
+                    | Operator::GreaterThanEqual
+                    | Operator::TildeEqual
+            ) =>
+        {
+            Some(format!("{}{}", specifier.operator(), latest))
+        }
+        // The bound loot add writes by default
+        [lower, upper]
+            if matches!(lower.operator(), Operator::GreaterThanEqual)
+                && matches!(upper.operator(), Operator::LessThan) =>
+        {
+            Some(bounded_requirement(latest, Bound::Major))
+        }
+        _ => None,
+    }
+}
+
+fn allows_version(requirement: &str, version: &str) -> bool {
+    match (parse_requirement(requirement), Version::from_str(version)) {
+        (Ok(specifiers), Ok(version)) => specifiers.contains(&version),
+        _ => false,
+    }
+}
+
+/// Prints the packages whose version changed between two locks
+pub fn print_version_changes(before: &[LockedPackage], after: &[LockedPackage]) {
+    let version = |packages: &[LockedPackage], name: &String| {
+        packages
+            .iter()
+            .find(|package| &package.name == name)
+            .map(|package| package.version.clone())
+    };
+
+    let names: BTreeSet<&String> = before
+        .iter()
+        .chain(after)
+        .map(|package| &package.name)
+        .collect();
+    let rows: Vec<(&String, Option<String>, Option<String>)> = names
+        .into_iter()
+        .map(|name| (name, version(before, name), version(after, name)))
+        .filter(|(_, before, after)| before != after)
+        .collect();
+
+    if rows.is_empty() {
+        println!("{color_green}Everything is up to date{color_reset}");
+        return;
+    }
+
+    let rows: Vec<(&str, Vec<String>)> = rows
+        .into_iter()
+        .map(|(name, before, after)| {
+            let color = match (&before, &after) {
+                (None, _) => color_green,
+                (_, None) => color_red,
+                _ => color_yellow,
+            };
+            (
+                color,
+                vec![
+                    name.clone(),
+                    before.unwrap_or_else(|| "-".to_owned()),
+                    after.unwrap_or_else(|| "-".to_owned()),
+                ],
+            )
+        })
+        .collect();
+    print_table(&["Package", "Before", "After"], &rows);
+}
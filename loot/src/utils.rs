@@ -1,3 +1,4 @@
+use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::Write;
 
@@ -42,3 +43,62 @@ pub fn clone_dir(origin: &std::path::PathBuf, target: &std::path::PathBuf) -> st
 
     Ok(())
 }
+
+/// Short stable name for a url or a path, used for dirs in the data dir
+pub fn short_hash(value: &str) -> String {
+    hex::encode(Sha256::digest(value.as_bytes()))[..16].to_owned()
+}
+
+/// Normalizes a package name as described in PEP 503, so `Foo_Bar` and `foo-bar` compare equal
+pub fn normalize_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    for c in name.trim().chars() {
+        if c == '-' || c == '_' || c == '.' {
+            if !normalized.ends_with('-') {
+                normalized.push('-');
+            }
+        } else {
+            normalized.push(c.to_ascii_lowercase());
+        }
+    }
+    normalized
+}
+
+/// Prints rows aligned under the headers, each row in its color
+pub fn print_table(headers: &[&str], rows: &[(&str, Vec<String>)]) {
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(column, header)| {
+            rows.iter()
+                .map(|(_, row)| row[column].len())
+                .chain(std::iter::once(header.len()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let line = |cells: Vec<&str>| {
+        let last = cells.len() - 1;
+        cells
+            .iter()
+            .enumerate()
+            .map(|(column, cell)| {
+                if column == last {
+                    cell.to_string()
+                } else {
+                    format!("{:width$}  ", cell, width = widths[column])
+                }
+            })
+            .collect::<String>()
+    };
+
+    println!("{}", line(headers.to_vec()));
+    for (color, row) in rows {
+        println!(
+            "{}{}{}",
+            color,
+            line(row.iter().map(|cell| cell.as_str()).collect()),
+            inline_colorization::color_reset
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use inline_colorization::*;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::app::AppExternal;
+use crate::error::{Context, LootError, CHECK_FAILED};
+use crate::utils::print_table;
+
+/// A file listed in a RECORD that is not what was installed
+struct Mismatch {
+    package: String,
+    file: String,
+    problem: &'static str,
+}
+
+/// Hashes every installed file again and compares it with the RECORD of its package
+pub async fn verify_venv(mut app: AppExternal<'_>) -> Result<i32, LootError> {
+    app.make_internal(None).await?;
+
+    let mut checked = 0;
+    let mut mismatches = Vec::new();
+    for site_packages in site_packages_dirs(&PathBuf::from(".lootbox").join("venv"))? {
+        let mut records: Vec<PathBuf> = fs::read_dir(&site_packages)
+            .context(|| LootError::venv(format!("Error reading {}", site_packages.display())))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.to_string_lossy().ends_with(".dist-info"))
+            .map(|dist_info| dist_info.join("RECORD"))
+            .filter(|record| record.is_file())
+            .collect();
+        records.sort();
+
+        for record in records {
+            checked += 1;
+            mismatches.extend(verify_record(&site_packages, &record)?);
+        }
+    }
+
+    if mismatches.is_empty() {
+        println!("{color_green}The {checked} installed packages match their RECORD{color_reset}");
+        return Ok(0);
+    }
+
+    let rows: Vec<(&str, Vec<String>)> = mismatches
+        .into_iter()
+        .map(|mismatch| {
+            (
+                color_red,
+                vec![mismatch.package, mismatch.file, mismatch.problem.to_owned()],
+            )
+        })
+        .collect();
+    print_table(&["Package", "File", "Problem"], &rows);
+    Ok(CHECK_FAILED)
+}
+
+/// `lib/python3.x/site-packages` of a venv, or `Lib/site-packages` on Windows
+fn site_packages_dirs(venv: &Path) -> Result<Vec<PathBuf>, LootError> {
+    if !venv.is_dir() {
+        return Err(LootError::venv("There is no venv").with_hint("Run loot sync first"));
+    }
+
+    let windows = venv.join("Lib").join("site-packages");
+    if windows.is_dir() {
+        return Ok(vec![windows]);
+    }
+
+    let mut dirs: Vec<PathBuf> = fs::read_dir(venv.join("lib"))
+        .context(|| LootError::venv("Error reading the venv"))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path().join("site-packages"))
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Files of a RECORD whose content changed or that are gone. Entries without a hash, like the RECORD itself or
+/// compiled files, are skipped
+fn verify_record(site_packages: &Path, record: &Path) -> Result<Vec<Mismatch>, LootError> {
+    let package = record
+        .parent()
+        .and_then(|dist_info| dist_info.file_stem())
+        .map(|name| name.to_string_lossy().replacen('-', " ", 1))
+        .unwrap_or_default();
+    let content = fs::read_to_string(record)
+        .context(|| LootError::venv(format!("Error reading {}", record.display())))?;
+
+    let mut mismatches = Vec::new();
+    for line in content.lines() {
+        // The path is the only column that can have commas, in which case it is quoted
+        let mut columns = line.rsplitn(3, ',');
+        let (Some(_size), Some(hash), Some(file)) =
+            (columns.next(), columns.next(), columns.next())
+        else {
+            continue;
+        };
+        let Some(expected) = hash.strip_prefix("sha256=") else {
+            continue;
+        };
+        let file = file.trim_matches('"');
+
+        let problem = match fs::read(site_packages.join(file)) {
+            Ok(bytes) if urlsafe_base64(&Sha256::digest(&bytes)) == expected => continue,
+            Ok(_) => "modified",
+            Err(_) => "missing",
+        };
+        mismatches.push(Mismatch {
+            package: package.clone(),
+            file: file.to_owned(),
+            problem,
+        });
+    }
+    Ok(mismatches)
+}
+
+/// Hashes in RECORD files are urlsafe base64 without padding
+fn urlsafe_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let block = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |block, (position, byte)| {
+                block | ((*byte as u32) << (16 - 8 * position))
+            });
+        for position in 0..=chunk.len() {
+            encoded.push(ALPHABET[((block >> (18 - 6 * position)) & 0x3f) as usize] as char);
+        }
+    }
+    encoded
+}
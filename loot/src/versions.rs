@@ -1,75 +1,372 @@
-use pep440_rs::{Version, VersionSpecifiers};
+use pep440_rs::{Version, VersionSpecifiers, VersionSpecifiersParseError};
+use std::collections::HashMap;
 use std::str::FromStr;
 
-use reqwest::get;
+use reqwest::Url;
 use serde::Deserialize;
 use serde_json;
 
-use crate::app::Config;
+use crate::app::{Bound, Config};
+use crate::cache::Cache;
+use crate::error::{Context, LootError};
+use crate::utils::normalize_name;
+
+pub const PYPI_INDEX: &str = "https://pypi.org/simple";
+
+/// Content types of the Simple API, JSON is preferred over HTML (PEP 691)
+const SIMPLE_API_ACCEPT: &str = "application/vnd.pypi.simple.v1+json, application/vnd.pypi.simple.v1+html;q=0.2, text/html;q=0.01";
+
+/// A file of a package in an index
+#[derive(Debug, Clone)]
+pub struct IndexFile {
+    pub filename: String,
+    pub url: String,
+    pub sha256: Option<String>,
+    pub requires_python: Option<String>,
+    pub yanked: bool,
+    /// The index serves the METADATA of the file at `{url}.metadata` (PEP 658)
+    pub core_metadata: bool,
+}
+
+impl IndexFile {
+    /// Version in the file name of a wheel or a source distribution
+    pub fn version(&self) -> Option<String> {
+        if let Some(wheel) = self.filename.strip_suffix(".whl") {
+            return wheel.split('-').nth(1).map(|version| version.to_owned());
+        }
+
+        let sdist = [".tar.gz", ".tgz", ".tar.bz2", ".zip"]
+            .iter()
+            .find_map(|extension| self.filename.strip_suffix(extension))?;
+        sdist
+            .rsplit_once('-')
+            .map(|(_, version)| version.to_owned())
+    }
+
+    pub fn is_wheel(&self) -> bool {
+        self.filename.ends_with(".whl")
+    }
+
+    /// Wether the file can be installed in the given python version. Like pip, a requires-python that can not be
+    /// parsed is ignored, some old releases have one
+    fn supports_python(&self, python_version: &Version) -> bool {
+        match &self.requires_python {
+            Some(requires_python) => VersionSpecifiers::from_str(requires_python)
+                .map_or(true, |specifiers| specifiers.contains(python_version)),
+            None => true,
+        }
+    }
+}
 
 #[derive(Deserialize, Debug, Clone)]
-struct Package {
-    releases: std::collections::HashMap<String, Vec<ReleaseData>>,
+struct SimpleProject {
+    files: Vec<SimpleFile>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
-struct ReleaseData {
-    yanked: bool,
+struct SimpleFile {
+    filename: String,
+    url: String,
+    #[serde(default)]
+    hashes: HashMap<String, String>,
+    #[serde(default, rename = "requires-python")]
     requires_python: Option<String>,
+    /// Either a boolean or the reason it was yanked
+    #[serde(default)]
+    yanked: serde_json::Value,
+    /// Either a boolean or the hashes of the metadata file
+    #[serde(default, rename = "core-metadata")]
+    core_metadata: serde_json::Value,
+    /// Name used before PEP 714
+    #[serde(default, rename = "dist-info-metadata")]
+    dist_info_metadata: serde_json::Value,
+}
+
+fn is_truthy(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => false,
+        serde_json::Value::Bool(value) => *value,
+        serde_json::Value::String(_) | serde_json::Value::Object(_) => true,
+        _ => false,
+    }
+}
+
+/// Files of a package listed by an index. Packages the index does not have have no files
+pub async fn get_index_files(
+    index: &str,
+    package: &str,
+    cache: &Cache,
+) -> Result<Vec<IndexFile>, LootError> {
+    let page_url = format!(
+        "{}/{}/",
+        index.trim_end_matches('/'),
+        normalize_name(package)
+    );
+
+    // A directory served as a file index has the pages as files, in either form
+    let page = if page_url.starts_with("file://") {
+        match cache
+            .fetch(&format!("{}index.json", page_url), SIMPLE_API_ACCEPT)
+            .await?
+        {
+            Some(page) => Some(page),
+            None => {
+                cache
+                    .fetch(&format!("{}index.html", page_url), SIMPLE_API_ACCEPT)
+                    .await?
+            }
+        }
+    } else {
+        cache.fetch(&page_url, SIMPLE_API_ACCEPT).await?
+    };
+    let Some((content_type, content)) = page else {
+        return Ok(Vec::new());
+    };
+
+    let page_url = Url::parse(&page_url)
+        .context(|| LootError::config(format!("Invalid index url {}", index)))?;
+    let content = String::from_utf8_lossy(&content);
+    if content_type.contains("json") {
+        parse_simple_json(&page_url, &content)
+    } else {
+        Ok(parse_simple_html(&page_url, &content))
+    }
+}
+
+fn parse_simple_json(page_url: &Url, content: &str) -> Result<Vec<IndexFile>, LootError> {
+    let project: SimpleProject = serde_json::from_str(content)
+        .context(|| LootError::network(format!("Invalid response from {}", page_url)))?;
+
+    Ok(project
+        .files
+        .into_iter()
+        .map(|file| IndexFile {
+            url: join_url(page_url, &file.url),
+            sha256: file.hashes.get("sha256").cloned(),
+            requires_python: file.requires_python,
+            yanked: is_truthy(&file.yanked),
+            core_metadata: is_truthy(&file.core_metadata) || is_truthy(&file.dist_info_metadata),
+            filename: file.filename,
+        })
+        .collect())
 }
 
-async fn get_global_package_info(package: &str) -> Package {
-    let url = format!("https://pypi.org/pypi/{package}/json");
-    let response = get(url).await.expect("Error fetching package info");
+/// Reads the anchors of a PEP 503 page, the hash comes in the url fragment and the rest as data attributes
+fn parse_simple_html(page_url: &Url, content: &str) -> Vec<IndexFile> {
+    let lowercase = content.to_ascii_lowercase();
+    let mut files = Vec::new();
 
-    let mut package_info: Package =
-        serde_json::from_str(&response.text().await.expect("Error fetching package info"))
-            .expect("Error parsing package info");
+    let mut position = 0;
+    while let Some(start) = lowercase[position..].find("<a ") {
+        let start = position + start;
+        let Some(tag_end) = lowercase[start..].find('>').map(|end| start + end) else {
+            break;
+        };
+        let text_end = lowercase[tag_end..]
+            .find("</a")
+            .map(|end| tag_end + end)
+            .unwrap_or(lowercase.len());
+        position = text_end;
 
-    package_info.releases.retain(|_, datas| {
-        if datas.len() == 0 {
-            return true;
+        let attributes = parse_html_attributes(&content[start + 2..tag_end]);
+        let Some(href) = attributes.get("href") else {
+            continue;
+        };
+        let (href, fragment) = match href.split_once('#') {
+            Some((href, fragment)) => (href, Some(fragment)),
+            None => (href.as_str(), None),
+        };
+        let url = join_url(page_url, href);
+
+        let mut filename = unescape_html(content[tag_end + 1..text_end].trim());
+        if filename.is_empty() {
+            filename = url.rsplit('/').next().unwrap_or_default().to_owned();
+        }
+
+        let metadata = attributes
+            .get("data-core-metadata")
+            .or_else(|| attributes.get("data-dist-info-metadata"));
+        files.push(IndexFile {
+            filename,
+            url,
+            sha256: fragment
+                .and_then(|fragment| fragment.strip_prefix("sha256="))
+                .map(|hash| hash.to_owned()),
+            requires_python: attributes.get("data-requires-python").cloned(),
+            yanked: attributes.contains_key("data-yanked"),
+            core_metadata: metadata.is_some_and(|metadata| metadata != "false"),
+        });
+    }
+
+    files
+}
+
+fn parse_html_attributes(tag: &str) -> HashMap<String, String> {
+    let chars: Vec<char> = tag.chars().collect();
+    let mut attributes = HashMap::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i].is_whitespace() || chars[i] == '/' {
+            i += 1;
+            continue;
         }
 
-        datas.iter().any(|data| {
-            let version_match;
-            if data.requires_python.is_none() {
-                version_match = true;
+        let mut name = String::new();
+        while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '=' {
+            name.push(chars[i].to_ascii_lowercase());
+            i += 1;
+        }
+
+        let mut value = String::new();
+        if i < chars.len() && chars[i] == '=' {
+            i += 1;
+            if i < chars.len() && (chars[i] == '"' || chars[i] == '\'') {
+                let quote = chars[i];
+                i += 1;
+                while i < chars.len() && chars[i] != quote {
+                    value.push(chars[i]);
+                    i += 1;
+                }
+                i += 1;
             } else {
-                let config = std::fs::read_to_string(crate::DEPENDENCIES_FILE)
-                    .expect("Could not read config");
-                let config: Config = toml::from_str(&config).expect("Could not parse config");
-                let python_version = Version::from_str(&config.python_version)
-                    .expect("Error parsing python version");
-
-                let version_requirements = VersionSpecifiers::from_str(
-                    &data.requires_python.as_ref().unwrap_or(&String::new()),
-                )
-                .expect("Error parsing python version requirements");
-
-                version_match = version_requirements.contains(&python_version);
+                while i < chars.len() && !chars[i].is_whitespace() {
+                    value.push(chars[i]);
+                    i += 1;
+                }
             }
-            !data.yanked && version_match
-        })
-    });
+        }
+
+        attributes.insert(name, unescape_html(&value));
+    }
 
-    package_info
+    attributes
 }
 
-pub async fn get_versions_of_package(package: &str) -> Vec<String> {
-    let package_info = get_global_package_info(package).await;
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
 
-    let version_numbers: Vec<String> = package_info
-        .releases
-        .into_iter()
-        .map(|(version, _)| version)
-        .collect();
+/// Urls in index pages can be relative to the page
+fn join_url(page_url: &Url, url: &str) -> String {
+    page_url
+        .join(url)
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| url.to_owned())
+}
+
+/// Versions with at least one file that is not yanked and supports the python version
+pub fn available_versions(files: &[IndexFile], python_version: &Version) -> Vec<String> {
+    let mut versions: Vec<String> = Vec::new();
+
+    for file in files {
+        if file.yanked || !file.supports_python(python_version) {
+            continue;
+        }
+        if let Some(version) = file.version() {
+            if !versions.contains(&version) {
+                versions.push(version);
+            }
+        }
+    }
+
+    versions
+}
+
+/// Versions with at least one file that is not yanked, whatever python they need
+pub fn released_versions(files: &[IndexFile]) -> Vec<String> {
+    let mut versions: Vec<String> = Vec::new();
+
+    for file in files.iter().filter(|file| !file.yanked) {
+        if let Some(version) = file.version() {
+            if !versions.contains(&version) {
+                versions.push(version);
+            }
+        }
+    }
+
+    versions
+}
+
+/// Highest version, pre-releases only if there is nothing else
+pub fn newest_version(versions: &[String]) -> Option<String> {
+    versions
+        .iter()
+        .filter_map(|version| Some((Version::from_str(version).ok()?, version)))
+        .max_by_key(|(parsed, _)| (!parsed.any_prerelease(), parsed.clone()))
+        .map(|(_, version)| version.clone())
+}
+
+pub async fn get_versions_of_package(
+    index: &str,
+    package: &str,
+    cache: &Cache,
+) -> Result<Vec<String>, LootError> {
+    let config = std::fs::read_to_string(crate::DEPENDENCIES_FILE)
+        .context(|| LootError::config("Could not read lootbox.toml"))?;
+    let config: Config =
+        toml::from_str(&config).context(|| LootError::config("lootbox.toml is not valid"))?;
+    let python_version = Version::from_str(&config.python_version).context(|| {
+        LootError::config(format!(
+            "Invalid python version {} in lootbox.toml",
+            config.python_version
+        ))
+    })?;
+
+    let files = get_index_files(index, package, cache).await?;
+
+    Ok(available_versions(&files, &python_version))
+}
+
+/// Requirement allowing the version and the newer ones the bound accepts
+pub fn bounded_requirement(version: &str, bound: Bound) -> String {
+    let parsed = Version::from_str(version).expect("Error parsing version");
+    let release = parsed.release();
+
+    match bound {
+        Bound::Exact => version.to_owned(),
+        // A single number has nothing to be compatible with, ~= needs two
+        Bound::Compatible if release.len() < 2 => format!(">={},<{}", version, release[0] + 1),
+        Bound::Compatible => format!("~={}", version),
+        Bound::Major => {
+            let upper = match release {
+                [0, minor, ..] => Version::new([0, minor + 1]),
+                [major, ..] => Version::new([major + 1]),
+                [] => unreachable!("Versions have at least one release number"),
+            };
+            format!(">={},<{}", version, upper)
+        }
+        Bound::Lower => format!(">={}", version),
+    }
+}
+
+/// Parses a requirement from lootbox.toml. A bare version is an exact pin and `*` allows any version
+pub fn parse_requirement(
+    requirement: &str,
+) -> Result<VersionSpecifiers, VersionSpecifiersParseError> {
+    let requirement = requirement.trim();
 
-    version_numbers
+    if requirement.is_empty() || requirement == "*" {
+        Ok(VersionSpecifiers::empty())
+    } else if Version::from_str(requirement).is_ok() {
+        VersionSpecifiers::from_str(&format!("=={requirement}"))
+    } else {
+        VersionSpecifiers::from_str(requirement)
+    }
 }
 
-pub async fn version_exists(package: &str, version: &String) -> bool {
-    let versions = get_versions_of_package(package).await;
+pub async fn version_exists(
+    index: &str,
+    package: &str,
+    version: &String,
+    cache: &Cache,
+) -> Result<bool, LootError> {
+    let versions = get_versions_of_package(index, package, cache).await?;
 
-    versions.contains(version)
+    Ok(versions.contains(version))
 }
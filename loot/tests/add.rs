@@ -0,0 +1,128 @@
+use std::fs;
+
+mod common;
+
+use common::{project_with_index, write_index, write_wheel, Project};
+
+fn requirements(project: &Project) -> toml::Value {
+    let config: toml::Value =
+        toml::from_str(&fs::read_to_string(project.path().join("lootbox.toml")).unwrap()).unwrap();
+    config["requirements"].clone()
+}
+
+#[test]
+fn add_several_packages() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &["app-utils"]),
+            ("app-lib", "1.1.0", &["app-utils"]),
+            ("app-utils", "1.0.0", &[]),
+            ("web-lib", "2.0.0", &["app-extras; extra == 'standard'"]),
+            ("app-extras", "1.0.0", &[]),
+        ],
+    );
+    let project = project_with_index(index.path(), "");
+
+    let output = project.loot(&[
+        "add",
+        "app-lib==1.0.0",
+        "web-lib[standard]",
+        "app-utils>=1; python_version >= '3'",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let requirements = requirements(&project);
+    assert_eq!(requirements["app-lib"].as_str(), Some("1.0.0"));
+    assert_eq!(
+        requirements["web-lib"]["version"].as_str(),
+        Some(">=2.0.0,<3")
+    );
+    assert_eq!(
+        requirements["web-lib"]["extras"][0].as_str(),
+        Some("standard")
+    );
+    assert_eq!(requirements["app-utils"]["version"].as_str(), Some(">=1"));
+    assert_eq!(
+        requirements["app-utils"]["markers"].as_str(),
+        Some("python_version >= '3'")
+    );
+
+    // Installed right away
+    let output = project.venv_python("import app_lib, app_utils, web_lib, app_extras");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(project.path().join("lootbox.lock").exists());
+
+    // An existing requirement is replaced however the name is written
+    let output = project.loot(&["add", "App_Lib", "Web.Lib"]);
+    assert!(output.status.success(), "{:?}", output);
+    let updated = crate::requirements(&project);
+    let updated = updated.as_table().unwrap();
+    assert_eq!(updated.len(), 3, "{:?}", updated);
+    assert_eq!(updated["app-lib"].as_str(), Some(">=1.1.0,<2"));
+    assert_eq!(updated["web-lib"]["extras"][0].as_str(), Some("standard"));
+}
+
+#[test]
+fn add_is_atomic() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &["app-utils<2"]),
+            ("app-utils", "1.0.0", &[]),
+            ("app-utils", "2.0.0", &[]),
+        ],
+    );
+    let project = project_with_index(index.path(), "");
+    let config = fs::read_to_string(project.path().join("lootbox.toml")).unwrap();
+
+    let output = project.loot(&["add", "app-lib", "app-utils>=2"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("app-utils"));
+    assert_eq!(
+        fs::read_to_string(project.path().join("lootbox.toml")).unwrap(),
+        config
+    );
+
+    // Invalid packages are rejected before anything is resolved
+    let output = project.loot(&["add", "app-lib", "missing-lib"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        fs::read_to_string(project.path().join("lootbox.toml")).unwrap(),
+        config
+    );
+
+    let output = project.venv_python("import app_lib");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn add_direct_reference_and_keep_packages_installed_by_hand() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(index.path(), &[("app-lib", "1.0.0", &[])]);
+    let project = project_with_index(index.path(), "");
+    let wheels = tempfile::tempdir().unwrap();
+    let direct = write_wheel(wheels.path(), "direct-lib", "1.0.0", &[]);
+    let by_hand = write_wheel(wheels.path(), "hand-lib", "1.0.0", &[]);
+
+    let output = project.loot(&["exec", "pip", "install", by_hand.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let reference = format!("direct-lib @ file://{}", direct.display());
+    let output = project.loot(&["add", &reference, "app-lib"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        requirements(&project)["direct-lib"]["url"].as_str(),
+        Some(format!("file://{}", direct.display()).as_str())
+    );
+    let output = project.venv_python("import direct_lib, app_lib, hand_lib");
+    assert!(output.status.success(), "{:?}", output);
+
+    // With --sync the venv is left with only what is locked
+    let output = project.loot(&["add", "--sync", "app-lib"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!project.venv_python("import hand_lib").status.success());
+    assert!(project.venv_python("import direct_lib").status.success());
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use common::{project_with_index, stdout, write_index, Project};
+
+#[test]
+fn exec_runs_in_the_venv() {
+    let project = Project::new("");
+
+    let output = project.loot(&["exec", "printenv", "VIRTUAL_ENV"]);
+    assert!(output.status.success(), "{:?}", output);
+    let venv = project.path().join(".lootbox").join("venv");
+    assert!(stdout(&output)
+        .trim()
+        .ends_with(&venv.to_string_lossy().to_string()));
+
+    let output = project.loot(&["exec", "which", "python"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout(&output).trim(),
+        venv.join("bin").join("python").to_string_lossy()
+    );
+}
+
+#[test]
+fn output_is_not_cut_by_any_word() {
+    let project = Project::new("");
+    std::fs::write(
+        project.path().join("src").join("main.py"),
+        "print('finalizau')\nprint('still running')\n",
+    )
+    .unwrap();
+
+    let output = project.loot(&["run"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("finalizau\nstill running\n"));
+}
+
+#[test]
+fn run_and_exec_exit_with_the_program() {
+    let project = Project::new("");
+    let main = project.path().join("src").join("main.py");
+
+    std::fs::write(&main, "import sys\nsys.exit(3)\n").unwrap();
+    let output = project.loot(&["run"]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+
+    let output = project.loot(&["exec", "python", "src/main.py"]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+
+    std::fs::write(
+        &main,
+        "import os, signal\nos.kill(os.getpid(), signal.SIGTERM)\n",
+    )
+    .unwrap();
+    let output = project.loot(&["run"]);
+    assert_eq!(output.status.code(), Some(128 + 15), "{:?}", output);
+
+    std::fs::write(&main, "print('done')\n").unwrap();
+    let output = project.loot(&["run"]);
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+
+    // Nothing to run is an invalid argument
+    let output = project.loot(&["exec"]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+}
+
+#[test]
+fn exec_of_a_program_that_can_not_start_exits_like_a_shell() {
+    let project = Project::new("");
+
+    let output = project.loot(&["exec", "does-not-exist"]);
+    assert_eq!(output.status.code(), Some(127), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Could not run does-not-exist"),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+
+    let output = project.loot(&["exec", "./src"]);
+    assert_eq!(output.status.code(), Some(126), "{:?}", output);
+}
+
+/// Arguments a shell would split, expand or run
+const ARGUMENTS: [&str; 8] = [
+    "two words",
+    "ünïcødé ✓",
+    "$HOME",
+    "a; echo injected",
+    "'single' \"double\"",
+    "*",
+    "`id`",
+    "-d",
+];
+
+#[test]
+fn arguments_reach_the_program_as_given() {
+    let project = Project::new("");
+    let print_argv = "import json, sys\nprint(json.dumps(sys.argv[1:], ensure_ascii=False))\n";
+    std::fs::write(project.path().join("src").join("main.py"), print_argv).unwrap();
+    let expected = format!(
+        "{}\n",
+        serde_json::to_string(&ARGUMENTS)
+            .unwrap()
+            .replace(',', ", ")
+    );
+
+    let output = project.loot(&[&["run", "--"][..], &ARGUMENTS].concat());
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).ends_with(&expected), "{}", stdout(&output));
+
+    let output = project.loot(&[&["exec", "python", "-c", print_argv][..], &ARGUMENTS].concat());
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), expected);
+}
+
+#[test]
+fn data_dir_with_spaces() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &["app-utils"]),
+            ("app-utils", "1.0.0", &[]),
+        ],
+    );
+    let project = project_with_index(index.path(), "app-lib = \"*\"\n");
+
+    // Like the Application Support dir of macOS
+    let data = project.root.path().join("Application Support");
+    std::os::unix::fs::symlink(project.root.path().join("data"), &data).unwrap();
+
+    let output = project
+        .loot_command(&["sync"])
+        .env("XDG_DATA_HOME", &data)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let output = project.venv_python("import app_lib, app_utils");
+    assert!(output.status.success(), "{:?}", output);
+}
@@ -0,0 +1,257 @@
+// Each test crate uses a different part of the helpers
+#![allow(dead_code)]
+
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A lootbox project in a temporary directory, using the python interpreter found in PATH
+/// as the installed python version so no python has to be built
+pub struct Project {
+    pub root: tempfile::TempDir,
+    pub python_version: String,
+    python: String,
+}
+
+impl Project {
+    pub fn new(requirements: &str) -> Project {
+        let root = tempfile::tempdir().expect("Error creating temp dir");
+
+        let python = python_output(&["-c", "import sys; print(sys.executable)"]);
+        let python_version =
+            python_output(&["-c", "import platform; print(platform.python_version())"]);
+
+        let install_bin = root
+            .path()
+            .join("data")
+            .join("py-lootbox")
+            .join("python_installs")
+            .join(&python_version)
+            .join("bin");
+        fs::create_dir_all(&install_bin).unwrap();
+        std::os::unix::fs::symlink(&python, install_bin.join("python3")).unwrap();
+
+        let project = Project {
+            root,
+            python_version,
+            python,
+        };
+        fs::create_dir_all(project.path().join("src")).unwrap();
+        fs::write(project.path().join("src").join("main.py"), "").unwrap();
+        fs::write(
+            project.path().join("lootbox.toml"),
+            project.config(requirements),
+        )
+        .unwrap();
+
+        project.create_venv();
+        fs::write(
+            project.path().join(".lootbox").join("lootbox.toml"),
+            project.config(""),
+        )
+        .unwrap();
+
+        project
+    }
+
+    /// The venv is made here instead of by lootbox, which would try to upgrade pip from the internet
+    pub fn create_venv(&self) {
+        let venv = self.path().join(".lootbox").join("venv");
+        let _ = fs::remove_dir_all(&venv);
+
+        let status = Command::new(&self.python)
+            .args(["-m", "venv"])
+            .arg(venv)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    /// Runs python code with the venv interpreter
+    pub fn venv_python(&self, code: &str) -> Output {
+        Command::new(
+            self.path()
+                .join(".lootbox")
+                .join("venv")
+                .join("bin")
+                .join("python"),
+        )
+        .args(["-c", code])
+        .output()
+        .unwrap()
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.root.path().join("project")
+    }
+
+    fn config(&self, requirements: &str) -> String {
+        format!(
+            "name = \"project\"\npython_version = \"{}\"\n\n[requirements]\n{}\n",
+            self.python_version, requirements
+        )
+    }
+
+    pub fn loot(&self, args: &[&str]) -> Output {
+        self.loot_command(args)
+            .output()
+            .expect("Error running loot")
+    }
+
+    /// Command running loot in the project, for tests that need the process while it runs
+    pub fn loot_command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_loot"));
+        command
+            .args(args)
+            .current_dir(self.path())
+            .env("XDG_DATA_HOME", self.root.path().join("data"));
+        command
+    }
+}
+
+fn python_output(args: &[&str]) -> String {
+    let output = Command::new("python3")
+        .args(args)
+        .output()
+        .expect("python3 not found");
+    String::from_utf8_lossy(&output.stdout).trim().to_owned()
+}
+
+/// Writes a minimal PEP 621 project
+pub fn write_python_project(path: &Path, name: &str, version: &str) {
+    fs::create_dir_all(path).unwrap();
+    fs::write(
+        path.join("pyproject.toml"),
+        format!(
+            "[project]\nname = \"{}\"\nversion = \"{}\"\ndependencies = []\n",
+            name, version
+        ),
+    )
+    .unwrap();
+}
+
+/// Writes a wheel with a single empty module to a directory, returning its path
+pub fn write_wheel(directory: &Path, name: &str, version: &str, requires_dist: &[&str]) -> PathBuf {
+    let module = name.replace('-', "_");
+    let dist_info = format!("{}-{}.dist-info", module, version);
+    let wheel = directory.join(format!("{}-{}-py3-none-any.whl", module, version));
+
+    let mut metadata = format!(
+        "Metadata-Version: 2.1\nName: {}\nVersion: {}\n",
+        name, version
+    );
+    for requirement in requires_dist {
+        metadata.push_str(&format!("Requires-Dist: {}\n", requirement));
+    }
+    let mut files = vec![
+        (format!("{}/__init__.py", module), String::new()),
+        (format!("{}/METADATA", dist_info), metadata),
+        (
+            format!("{}/WHEEL", dist_info),
+            "Wheel-Version: 1.0\nGenerator: loot-tests\nRoot-Is-Purelib: true\nTag: py3-none-any\n"
+                .to_owned(),
+        ),
+    ];
+    let mut record: String = files
+        .iter()
+        .map(|(path, content)| {
+            format!(
+                "{},sha256={},{}\n",
+                path,
+                record_hash(content.as_bytes()),
+                content.len()
+            )
+        })
+        .collect();
+    record.push_str(&format!("{}/RECORD,,\n", dist_info));
+    files.push((format!("{}/RECORD", dist_info), record));
+
+    fs::create_dir_all(directory).unwrap();
+    let mut zip = zip::ZipWriter::new(fs::File::create(&wheel).unwrap());
+    for (path, content) in files {
+        zip.start_file(path, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    wheel
+}
+
+/// Hash of a file in a RECORD, urlsafe base64 without padding
+fn record_hash(content: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    let mut encoded = String::new();
+    for chunk in Sha256::digest(content).chunks(3) {
+        let block = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |block, (position, byte)| {
+                block | ((*byte as u32) << (16 - 8 * position))
+            });
+        for position in 0..=chunk.len() {
+            encoded.push(ALPHABET[((block >> (18 - 6 * position)) & 0x3f) as usize] as char);
+        }
+    }
+    encoded
+}
+
+pub fn sha256(file: &Path) -> String {
+    hex::encode(Sha256::digest(fs::read(file).unwrap()))
+}
+
+/// Writes the PEP 503 page of a package, each wheel with its extra anchor attributes
+pub fn write_html_page(index: &Path, package: &str, wheels: &[(PathBuf, &str)]) {
+    let anchors: String = wheels
+        .iter()
+        .map(|(wheel, attributes)| {
+            format!(
+                "<a href=\"{0}#sha256={1}\" {2}>{0}</a><br/>\n",
+                wheel.file_name().unwrap().to_string_lossy(),
+                sha256(wheel),
+                attributes
+            )
+        })
+        .collect();
+
+    fs::write(
+        index.join(package).join("index.html"),
+        format!("<!DOCTYPE html>\n<html><body>\n{}</body></html>\n", anchors),
+    )
+    .unwrap();
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+/// Writes a file index with one wheel per release, given as `(package, version, requires_dist)`
+pub fn write_index(index: &Path, releases: &[(&str, &str, &[&str])]) {
+    let mut packages: Vec<&str> = releases.iter().map(|(package, _, _)| *package).collect();
+    packages.sort();
+    packages.dedup();
+
+    for package in packages {
+        let directory = index.join(package);
+        let wheels: Vec<_> = releases
+            .iter()
+            .filter(|(other, _, _)| *other == package)
+            .map(|(_, version, requires_dist)| {
+                (write_wheel(&directory, package, version, requires_dist), "")
+            })
+            .collect();
+        write_html_page(index, package, &wheels);
+    }
+}
+
+/// Project whose packages all come from a file index
+pub fn project_with_index(index: &Path, requirements: &str) -> Project {
+    Project::new(&format!(
+        "{}\n[[index]]\nname = \"mirror\"\nurl = \"file://{}\"\ndefault = true\n",
+        requirements,
+        index.display()
+    ))
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::process::Command;
+
+mod common;
+
+use common::{project_with_index, stdout, write_index, write_python_project, write_wheel, Project};
+
+#[test]
+fn path_dependency() {
+    let project = Project::new(r#"shared-lib = { path = "../shared-lib", editable = true }"#);
+    write_python_project(
+        &project.root.path().join("shared-lib"),
+        "shared-lib",
+        "0.1.0",
+    );
+
+    let output = project.loot(&["sync", "--dry-run"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("+ shared-lib 0.1.0"));
+
+    // The bundle installs it from the path, it is in no index
+    let output = project.loot(&["bundle"]);
+    assert!(output.status.success(), "{:?}", output);
+    let requirements =
+        fs::read_to_string(project.path().join("target").join("requirements.txt")).unwrap();
+    assert!(
+        requirements.contains("-e ../shared-lib\n"),
+        "{}",
+        requirements
+    );
+    assert!(!requirements.contains("=="), "{}", requirements);
+
+    // pip would refuse the whole file for the line without a hash
+    let output = project.loot(&["export", "--hashes"]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("There are no hashes of shared-lib"));
+}
+
+#[test]
+fn pkg_info_only_with_static_requirements() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(index.path(), &[("app-utils", "1.0.0", &[])]);
+    let project = project_with_index(index.path(), r#"legacy-lib = { path = "../legacy-lib" }"#);
+    let directory = project.root.path().join("legacy-lib");
+    fs::create_dir_all(&directory).unwrap();
+    let write_pkg_info = |headers: &str| {
+        fs::write(
+            directory.join("PKG-INFO"),
+            format!("Name: legacy-lib\nVersion: 1.0.0\n{}", headers),
+        )
+        .unwrap();
+    };
+
+    write_pkg_info("Metadata-Version: 2.2\nRequires-Dist: app-utils\n");
+    let output = project.loot(&["sync", "--dry-run"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("+ legacy-lib 1.0.0"));
+    assert!(stdout(&output).contains("+ app-utils 1.0.0"));
+
+    // Older or dynamic requirements are read from a build instead, which fails without a setup.py
+    for headers in [
+        "Metadata-Version: 1.0\n",
+        "Metadata-Version: 2.2\nDynamic: Requires-Dist\n",
+    ] {
+        write_pkg_info(headers);
+        let output = project.loot(&["sync", "--dry-run"]);
+        assert_eq!(output.status.code(), Some(5), "{:?}", output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Error building"), "{}", stderr);
+    }
+}
+
+#[test]
+fn git_dependency() {
+    let project = Project::new("");
+    let repository = project.root.path().join("git-lib");
+    write_python_project(&repository, "git-lib", "1.0.0");
+
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(["-c", "user.name=loot", "-c", "user.email=loot@example.com"])
+            .args(args)
+            .current_dir(&repository)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    git(&["init", "--quiet"]);
+    git(&["add", "."]);
+    git(&["commit", "--quiet", "-m", "1.0.0"]);
+    git(&["tag", "v1.0.0"]);
+    write_python_project(&repository, "git-lib", "2.0.0");
+    git(&["commit", "--quiet", "-am", "2.0.0"]);
+
+    fs::write(
+        project.path().join("lootbox.toml"),
+        format!(
+            "name = \"project\"\npython_version = \"{}\"\n\n[requirements]\ngit-lib = {{ git = \"{}\", rev = \"v1.0.0\" }}\n",
+            project.python_version,
+            repository.display()
+        ),
+    )
+    .unwrap();
+    let output = project.loot(&["sync", "--dry-run"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("+ git-lib 1.0.0"));
+
+    // Without a revision the default branch is used
+    fs::write(
+        project.path().join("lootbox.toml"),
+        format!(
+            "name = \"project\"\npython_version = \"{}\"\n\n[requirements]\ngit-lib = {{ git = \"{}\" }}\n",
+            project.python_version,
+            repository.display()
+        ),
+    )
+    .unwrap();
+    let output = project.loot(&["sync", "--dry-run"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("+ git-lib 2.0.0"));
+
+    // A revision the repository does not have is a config error
+    fs::write(
+        project.path().join("lootbox.toml"),
+        format!(
+            "name = \"project\"\npython_version = \"{}\"\n\n[requirements]\ngit-lib = {{ git = \"{}\", rev = \"v9\" }}\n",
+            project.python_version,
+            repository.display()
+        ),
+    )
+    .unwrap();
+    let output = project.loot(&["sync", "--dry-run"]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Revision v9 not found"), "{}", stderr);
+}
+
+#[test]
+fn url_dependency() {
+    let project = Project::new("");
+    let wheel = write_wheel(project.root.path(), "url-lib", "0.3.0", &[]);
+
+    fs::write(
+        project.path().join("lootbox.toml"),
+        format!(
+            "name = \"project\"\npython_version = \"{}\"\n\n[requirements]\nurl-lib = {{ url = \"file://{}\" }}\n",
+            project.python_version,
+            wheel.display()
+        ),
+    )
+    .unwrap();
+    let output = project.loot(&["sync", "--dry-run"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("+ url-lib 0.3.0"));
+
+    let output = project.loot(&["export", "--hashes"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains(" \\\n    --hash=sha256:"));
+}
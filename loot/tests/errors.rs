@@ -0,0 +1,155 @@
+use std::fs;
+
+mod common;
+
+use common::{project_with_index, write_index, Project};
+
+#[test]
+fn missing_config_is_explained() {
+    let project = Project::new("");
+    fs::remove_file(project.path().join("lootbox.toml")).unwrap();
+
+    let output = project.loot(&["sync"]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("error: There is no lootbox.toml here"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("hint: Run it inside a project"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("Run with --debug"), "{}", stderr);
+    assert!(!stderr.contains("caused by"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+
+    let output = project.loot(&["sync", "--debug"]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("caused by: "), "{}", stderr);
+    assert!(!stderr.contains("Run with --debug"), "{}", stderr);
+}
+
+#[test]
+fn missing_python_is_an_interpreter_error() {
+    let project = Project::new("");
+    fs::write(
+        project.path().join("lootbox.toml"),
+        "name = \"project\"\npython_version = \"3.0.1\"\n\n[requirements]\n",
+    )
+    .unwrap();
+
+    let output = project.loot(&["sync"]);
+    assert_eq!(output.status.code(), Some(6), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Python 3.0.1 is not installed"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("hint: Install it with loot install 3.0.1"));
+}
+
+#[test]
+fn conflicts_are_resolution_errors() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &["app-utils<1"]),
+            ("app-utils", "0.9.0", &[]),
+            ("app-utils", "1.2.0", &[]),
+        ],
+    );
+    let project = project_with_index(index.path(), "app-lib = \"*\"\napp-utils = \"1.2.0\"\n");
+
+    let output = project.loot(&["sync"]);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+
+    let output = project.loot(&["--error-format", "json", "sync"]);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["kind"], "resolution");
+    assert!(error["message"]
+        .as_str()
+        .unwrap()
+        .contains("version solving failed"));
+    assert_eq!(error["conflicts"][0]["package"], "app-utils");
+}
+
+#[test]
+fn invalid_input_is_a_config_error() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(index.path(), &[("app-lib", "1.0.0", &[])]);
+    let project = project_with_index(index.path(), "app-lib = \"*\"\n\n[groups.dev]\n");
+
+    for args in [
+        &["sync", "--group", "nope"][..],
+        &["export", "--group", "nope"],
+        &["import", "missing-requirements.txt"],
+    ] {
+        let output = project.loot(args);
+        assert_eq!(output.status.code(), Some(3), "{:?}", output);
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("panicked"));
+    }
+    let output = project.loot(&["sync", "--group", "nope"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("error: Group nope does not exist"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("hint: The groups in lootbox.toml are dev"),
+        "{}",
+        stderr
+    );
+
+    let config = fs::read_to_string(project.path().join("lootbox.toml")).unwrap();
+    for (before, after) in [
+        ("app-lib = \"*\"", "app-lib = \"not a version\""),
+        (
+            "app-lib = \"*\"",
+            "app-lib = { version = \"*\", index = \"nope\" }",
+        ),
+    ] {
+        fs::write(
+            project.path().join("lootbox.toml"),
+            config.replace(before, after),
+        )
+        .unwrap();
+        let output = project.loot(&["sync"]);
+        assert_eq!(output.status.code(), Some(3), "{:?}", output);
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("panicked"));
+    }
+}
+
+#[test]
+fn broken_project_files_are_explained() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(index.path(), &[("app-lib", "1.0.0", &[])]);
+    let project = project_with_index(index.path(), "app-lib = \"*\"\n");
+    let output = project.loot(&["sync"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    // A lock left with merge conflict markers
+    let lock = project.path().join("lootbox.lock");
+    let locked = fs::read_to_string(&lock).unwrap();
+    fs::write(&lock, format!("<<<<<<< HEAD\n{}", locked)).unwrap();
+    let output = project.loot(&["sync"]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("lootbox.lock is not valid"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    fs::write(&lock, locked).unwrap();
+
+    fs::remove_file(project.path().join(".lootbox").join("lootbox.toml")).unwrap();
+    let output = project.loot(&["sync"]);
+    assert_eq!(output.status.code(), Some(7), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("hint: Delete .lootbox"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
@@ -0,0 +1,147 @@
+use std::fs;
+
+mod common;
+
+use common::{project_with_index, sha256, stdout, write_index, Project};
+
+fn project() -> (tempfile::TempDir, Project) {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &["app-utils"]),
+            ("app-utils", "1.0.0", &[]),
+            ("marked-lib", "1.0.0", &["app-utils"]),
+            ("dev-tool", "1.0.0", &["marked-lib"]),
+        ],
+    );
+    let project = project_with_index(
+        index.path(),
+        "app-lib = \">=1\"\nmarked-lib = { version = \"*\", markers = \"python_version >= '3'\" }
+
+[groups.dev]
+dev-tool = \"1.0.0\"
+",
+    );
+
+    (index, project)
+}
+
+fn export(project: &Project, args: &[&str]) -> String {
+    let output = project.loot(&[&["export"], args].concat());
+    assert!(output.status.success(), "{:?}", output);
+    stdout(&output)
+}
+
+#[test]
+fn export_requirements() {
+    let (index, project) = project();
+
+    // marked-lib is needed by dev-tool whatever the python version is
+    let exported = export(&project, &[]);
+    assert!(
+        exported.ends_with(&format!(
+            "--index-url file://{}
+app-lib==1.0.0
+app-utils==1.0.0
+dev-tool==1.0.0
+marked-lib==1.0.0
+",
+            index.path().display()
+        )),
+        "{}",
+        exported
+    );
+
+    // Without the group only the markers of lootbox.toml are left
+    let exported = export(&project, &["--no-dev"]);
+    assert!(exported.contains("app-lib==1.0.0\n"), "{}", exported);
+    assert!(
+        exported.contains("marked-lib==1.0.0 ; python_version >= '3'\n"),
+        "{}",
+        exported
+    );
+    assert!(!exported.contains("dev-tool"), "{}", exported);
+
+    let exported = export(&project, &["--no-dev", "--hashes"]);
+    let wheel = index
+        .path()
+        .join("app-lib")
+        .join("app_lib-1.0.0-py3-none-any.whl");
+    assert!(
+        exported.contains(&format!(
+            "app-lib==1.0.0 \\\n    --hash=sha256:{}\n",
+            sha256(&wheel)
+        )),
+        "{}",
+        exported
+    );
+
+    // The venv is left alone
+    assert!(!project
+        .path()
+        .join(".lootbox")
+        .join("lootbox.lock")
+        .exists());
+    assert!(!project.venv_python("import app_lib").status.success());
+}
+
+#[test]
+fn export_pyproject_and_pylock() {
+    let (index, project) = project();
+
+    let output = project.path().join("exported.toml");
+    export(
+        &project,
+        &[
+            "--format",
+            "pyproject",
+            "--output",
+            output.to_str().unwrap(),
+        ],
+    );
+    let pyproject: toml::Value = toml::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+    assert_eq!(pyproject["project"]["name"].as_str(), Some("project"));
+    assert_eq!(
+        pyproject["project"]["dependencies"],
+        toml::Value::from(vec!["app-lib>=1", "marked-lib; python_version >= '3'"])
+    );
+    assert_eq!(
+        pyproject["dependency-groups"]["dev"],
+        toml::Value::from(vec!["dev-tool==1.0.0"])
+    );
+
+    let pylock: toml::Value = toml::from_str(&export(&project, &["--format", "pylock"])).unwrap();
+    assert_eq!(pylock["lock-version"].as_str(), Some("1.0"));
+    assert_eq!(pylock["dependency-groups"], toml::Value::from(vec!["dev"]));
+
+    let packages = pylock["packages"].as_array().unwrap();
+    let package = |name: &str| {
+        packages
+            .iter()
+            .find(|package| package["name"].as_str() == Some(name))
+            .unwrap()
+    };
+    assert!(package("app-utils").get("marker").is_none());
+    assert_eq!(
+        package("dev-tool")["marker"].as_str(),
+        Some("'dev' in dependency_groups")
+    );
+    // Needed by the main requirements with their marker, or by the dev group
+    assert_eq!(
+        package("marked-lib")["marker"].as_str(),
+        Some("('dev' in dependency_groups) or (python_version >= '3')")
+    );
+    let wheel = index
+        .path()
+        .join("app-lib")
+        .join("app_lib-1.0.0-py3-none-any.whl");
+    assert_eq!(package("app-lib")["version"].as_str(), Some("1.0.0"));
+    assert_eq!(
+        package("app-lib")["wheels"][0]["hashes"]["sha256"].as_str(),
+        Some(sha256(&wheel).as_str())
+    );
+
+    let output = project.loot(&["export", "--format", "pyproject", "--hashes"]);
+    assert!(!output.status.success());
+}
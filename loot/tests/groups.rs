@@ -0,0 +1,57 @@
+use std::fs;
+
+mod common;
+
+use common::{project_with_index, write_index};
+
+#[test]
+fn sync_and_bundle_choose_groups() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &[]),
+            ("test-lib", "1.0.0", &["test-utils"]),
+            ("test-utils", "1.0.0", &[]),
+            ("docs-lib", "1.0.0", &[]),
+        ],
+    );
+    let project = project_with_index(
+        index.path(),
+        "app-lib = \"*\"\n\n[groups.dev]\ntest-lib = \"*\"\n\n[groups.docs]\ndocs-lib = \"*\"\n",
+    );
+    let installed = |module: &str| {
+        project
+            .venv_python(&format!("import {}", module))
+            .status
+            .success()
+    };
+
+    let output = project.loot(&["sync", "--group", "dev"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(installed("app_lib"));
+    assert!(installed("test_lib"));
+    assert!(installed("test_utils"));
+    assert!(!installed("docs_lib"));
+
+    let output = project.loot(&["sync"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(installed("docs_lib"));
+
+    // Groups left out are removed from the venv
+    let output = project.loot(&["sync", "--no-dev"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(installed("app_lib"));
+    assert!(!installed("test_lib"));
+    assert!(!installed("test_utils"));
+    assert!(!installed("docs_lib"));
+
+    let output = project.loot(&["bundle"]);
+    assert!(output.status.success(), "{:?}", output);
+    let requirements =
+        fs::read_to_string(project.path().join("target").join("requirements.txt")).unwrap();
+    assert!(requirements.contains("app-lib==1.0.0"), "{}", requirements);
+    assert!(!requirements.contains("test-lib"), "{}", requirements);
+    assert!(!requirements.contains("test-utils"), "{}", requirements);
+    assert!(!requirements.contains("docs-lib"), "{}", requirements);
+}
@@ -0,0 +1,174 @@
+use std::fs;
+
+mod common;
+
+use common::{stdout, Project};
+
+fn import(project: &Project, file: &str, content: &str) -> (String, toml::Value) {
+    fs::write(project.path().join(file), content).unwrap();
+    let output = project.loot(&["import", file]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let config = fs::read_to_string(project.path().join("lootbox.toml")).unwrap();
+    (stdout(&output), toml::from_str(&config).unwrap())
+}
+
+#[test]
+fn import_requirements_txt() {
+    let project = Project::new("");
+    fs::create_dir_all(project.path().join("requirements")).unwrap();
+    fs::write(
+        project.path().join("requirements").join("base.txt"),
+        "Django>=4.2 # the web framework\nrequests\n",
+    )
+    .unwrap();
+    fs::write(
+        project.path().join("requirements").join("constraints.txt"),
+        "requests==2.31.0\nnot-required==1.0\n",
+    )
+    .unwrap();
+
+    let (output, config) = import(
+        &project,
+        "requirements/main.txt",
+        "-r base.txt
+-c constraints.txt
+--index-url https://example.com/simple
+uvicorn[standard]>=0.30 ; python_version >= '3.8'
+attrs==23.2.0 \\
+    --hash=sha256:0123456789abcdef
+internal-tools @ git+https://github.com/example/internal-tools@v1.2.0
+not a requirement
+",
+    );
+    let requirements = &config["requirements"];
+
+    assert_eq!(requirements["Django"].as_str(), Some(">=4.2"));
+    assert_eq!(requirements["requests"].as_str(), Some("2.31.0"));
+    assert!(requirements.get("not-required").is_none());
+    assert_eq!(requirements["uvicorn"]["version"].as_str(), Some(">=0.30"));
+    assert_eq!(
+        requirements["uvicorn"]["extras"][0].as_str(),
+        Some("standard")
+    );
+    assert_eq!(
+        requirements["uvicorn"]["markers"].as_str(),
+        Some("python_version >= '3.8'")
+    );
+    assert_eq!(requirements["attrs"].as_str(), Some("23.2.0"));
+    assert_eq!(
+        requirements["internal-tools"]["git"].as_str(),
+        Some("https://github.com/example/internal-tools")
+    );
+    assert_eq!(
+        requirements["internal-tools"]["rev"].as_str(),
+        Some("v1.2.0")
+    );
+
+    assert!(output.contains("Imported 5 requirements"), "{}", output);
+    assert!(
+        output.contains("--index-url https://example.com/simple"),
+        "{}",
+        output
+    );
+    assert!(output.contains("not a requirement"), "{}", output);
+}
+
+#[test]
+fn import_pyproject() {
+    let project = Project::new("");
+
+    let (output, config) = import(
+        &project,
+        "pyproject.toml",
+        r#"
+[project]
+name = "service"
+requires-python = ">=99"
+dependencies = ["httpx>=0.27", "rich"]
+
+[project.optional-dependencies]
+cli = ["click>=8"]
+
+[dependency-groups]
+test = ["pytest>=8", { include-group = "lint" }]
+
+[tool.poetry.dependencies]
+python = "^3.10"
+pydantic = "^2.5.1"
+tomli = { version = "~1.2", python = "<3.11" }
+legacy = "^1.0 || ^2.0"
+
+[tool.poetry.group.dev.dependencies]
+ruff = "0.4.1"
+"#,
+    );
+
+    assert_eq!(config["requirements"]["httpx"].as_str(), Some(">=0.27"));
+    assert_eq!(config["requirements"]["rich"].as_str(), Some("*"));
+    assert_eq!(config["groups"]["cli"]["click"].as_str(), Some(">=8"));
+    assert_eq!(config["groups"]["test"]["pytest"].as_str(), Some(">=8"));
+    assert_eq!(
+        config["requirements"]["pydantic"].as_str(),
+        Some(">=2.5.1,<3")
+    );
+    assert_eq!(
+        config["requirements"]["tomli"]["version"].as_str(),
+        Some(">=1.2,<1.3")
+    );
+    assert_eq!(
+        config["requirements"]["tomli"]["markers"].as_str(),
+        Some("python_version < '3.11'")
+    );
+    assert_eq!(config["groups"]["dev"]["ruff"].as_str(), Some("0.4.1"));
+
+    assert!(output.contains("requires python >=99"), "{}", output);
+    assert!(output.contains("include-group"), "{}", output);
+    assert!(output.contains("legacy"), "{}", output);
+}
+
+#[test]
+fn import_pipfile() {
+    let project = Project::new("");
+
+    let (output, config) = import(
+        &project,
+        "Pipfile",
+        r#"
+[[source]]
+url = "https://pypi.org/simple"
+verify_ssl = true
+name = "pypi"
+
+[[source]]
+url = "https://example.com/simple"
+verify_ssl = true
+name = "internal"
+
+[packages]
+requests = "*"
+flask = "==3.0.0"
+internal-lib = { version = ">=1.0", index = "internal" }
+
+[dev-packages]
+pytest = { version = ">=8", extras = ["testing"] }
+
+[requires]
+python_version = "2.7"
+"#,
+    );
+
+    assert_eq!(config["requirements"]["requests"].as_str(), Some("*"));
+    assert_eq!(config["requirements"]["flask"].as_str(), Some("3.0.0"));
+    assert_eq!(
+        config["requirements"]["internal-lib"]["index"].as_str(),
+        Some("internal")
+    );
+    assert_eq!(
+        config["groups"]["dev"]["pytest"]["extras"][0].as_str(),
+        Some("testing")
+    );
+    assert_eq!(config["index"][0]["name"].as_str(), Some("internal"));
+    assert_eq!(config["index"].as_array().unwrap().len(), 1);
+    assert!(output.contains("requires python ==2.7.*"), "{}", output);
+}
@@ -0,0 +1,111 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+mod common;
+
+use common::{sha256, stdout, write_html_page, write_wheel, Project};
+
+fn file_name(file: &Path) -> String {
+    file.file_name().unwrap().to_string_lossy().to_string()
+}
+
+/// Writes the PEP 691 page of a package, each wheel with its extra file keys
+fn write_json_page(index: &Path, package: &str, wheels: &[(PathBuf, &str)]) {
+    let files: Vec<String> = wheels
+        .iter()
+        .map(|(wheel, keys)| {
+            format!(
+                "{{\"filename\": \"{0}\", \"url\": \"{0}\", \"hashes\": {{\"sha256\": \"{1}\"}}{2}}}",
+                file_name(wheel),
+                sha256(wheel),
+                keys
+            )
+        })
+        .collect();
+
+    fs::write(
+        index.join(package).join("index.json"),
+        format!(
+            "{{\"meta\": {{\"api-version\": \"1.0\"}}, \"name\": \"{}\", \"files\": [{}]}}",
+            package,
+            files.join(", ")
+        ),
+    )
+    .unwrap();
+}
+
+#[test]
+fn pinned_index() {
+    let index = tempfile::tempdir().unwrap();
+    let package = index.path().join("internal-lib");
+    let wheels = [
+        (write_wheel(&package, "internal-lib", "1.0.0", &[]), ""),
+        // Some old releases on PyPI have a requires-python that is not valid, it is ignored
+        (
+            write_wheel(&package, "internal-lib", "2.0.0", &[]),
+            "data-requires-python=\"&gt;=3.x\"",
+        ),
+        (
+            write_wheel(&package, "internal-lib", "3.0.0", &[]),
+            "data-requires-python=\"&gt;=99\"",
+        ),
+    ];
+    write_html_page(index.path(), "internal-lib", &wheels);
+
+    let project = Project::new(&format!(
+        "internal-lib = {{ version = \">=1\", index = \"internal\" }}\n\n[[index]]\nname = \"internal\"\nurl = \"file://{}\"\n",
+        index.path().display()
+    ));
+
+    // The release that does not support the python version is skipped
+    let output = project.loot(&["sync", "--dry-run"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("+ internal-lib 2.0.0"));
+
+    let output = project.loot(&["sync"]);
+    assert!(output.status.success(), "{:?}", output);
+    let output = project.venv_python("import internal_lib");
+    assert!(output.status.success(), "{:?}", output);
+
+    let lock = fs::read_to_string(project.path().join("lootbox.lock")).unwrap();
+    assert!(lock.contains(&format!("source = \"file://{}\"", index.path().display())));
+    assert!(lock.contains(&format!("hash = \"sha256:{}\"", sha256(&wheels[1].0))));
+}
+
+#[test]
+fn default_index() {
+    let index = tempfile::tempdir().unwrap();
+    let app = index.path().join("app-lib");
+    let utils = index.path().join("app-utils");
+    write_json_page(
+        index.path(),
+        "app-lib",
+        &[(
+            write_wheel(&app, "app-lib", "1.0.0", &["app-utils>=0.2"]),
+            ", \"core-metadata\": false",
+        )],
+    );
+    write_json_page(
+        index.path(),
+        "app-utils",
+        &[
+            (write_wheel(&utils, "app-utils", "0.1.0", &[]), ""),
+            (write_wheel(&utils, "app-utils", "0.2.0", &[]), ""),
+            (
+                write_wheel(&utils, "app-utils", "0.3.0", &[]),
+                ", \"yanked\": \"Broken\"",
+            ),
+        ],
+    );
+
+    let project = Project::new(&format!(
+        "app-lib = \"*\"\n\n[[index]]\nname = \"mirror\"\nurl = \"file://{}\"\ndefault = true\n",
+        index.path().display()
+    ));
+
+    // Yanked releases are skipped
+    let output = project.loot(&["sync", "--dry-run"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("+ app-lib 1.0.0"));
+    assert!(stdout(&output).contains("+ app-utils 0.2.0"));
+}
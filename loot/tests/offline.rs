@@ -0,0 +1,153 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+mod common;
+
+use common::{stdout, write_html_page, write_wheel, Project};
+
+/// Static file server answering conditional requests with a 304, like a real index. Requests are logged as
+/// `{status} {path}`
+const INDEX_SERVER: &str = r#"
+import functools, hashlib, http.server, os, sys
+
+directory, log = sys.argv[1], sys.argv[2]
+
+class Handler(http.server.SimpleHTTPRequestHandler):
+    def do_GET(self):
+        path = self.translate_path(self.path)
+        if os.path.isdir(path):
+            path = os.path.join(path, "index.html")
+        if not os.path.isfile(path):
+            self.send_error(404)
+            return
+
+        content = open(path, "rb").read()
+        etag = '"' + hashlib.sha256(content).hexdigest() + '"'
+        status = 304 if self.headers.get("If-None-Match") == etag else 200
+        with open(log, "a") as file:
+            file.write(f"{status} {self.path}\n")
+
+        self.send_response(status)
+        self.send_header("ETag", etag)
+        if status == 200:
+            self.send_header("Content-Type", "text/html" if path.endswith(".html") else "application/octet-stream")
+            self.send_header("Content-Length", str(len(content)))
+        self.end_headers()
+        if status == 200:
+            self.wfile.write(content)
+
+    def log_message(self, *args):
+        pass
+
+server = http.server.HTTPServer(("127.0.0.1", 0), functools.partial(Handler, directory=directory))
+print(server.server_address[1], flush=True)
+server.serve_forever()
+"#;
+
+struct IndexServer {
+    child: Child,
+    port: String,
+}
+
+impl IndexServer {
+    fn start(directory: &Path, log: &Path) -> IndexServer {
+        let mut child = Command::new("python3")
+            .args(["-c", INDEX_SERVER])
+            .arg(directory)
+            .arg(log)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let mut port = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut port)
+            .unwrap();
+
+        IndexServer {
+            child,
+            port: port.trim().to_owned(),
+        }
+    }
+}
+
+impl Drop for IndexServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn offline_from_cache() {
+    let index = tempfile::tempdir().unwrap();
+    let package = index.path().join("internal-lib");
+    write_html_page(
+        index.path(),
+        "internal-lib",
+        &[
+            (write_wheel(&package, "internal-lib", "1.0.0", &[]), ""),
+            (write_wheel(&package, "internal-lib", "2.0.0", &[]), ""),
+        ],
+    );
+    let log = index.path().join("requests.log");
+    let server = IndexServer::start(index.path(), &log);
+    let port = server.port.clone();
+
+    let config = |requirement: &str| {
+        format!(
+            "internal-lib = {{ version = \"{}\", index = \"internal\" }}\n\n[[index]]\nname = \"internal\"\nurl = \"http://127.0.0.1:{}/\"\n",
+            requirement, port
+        )
+    };
+    let project = Project::new(&config(">=1"));
+    let write_config = |requirement: &str| {
+        fs::write(
+            project.path().join("lootbox.toml"),
+            format!(
+                "name = \"project\"\npython_version = \"{}\"\n\n[requirements]\n{}",
+                project.python_version,
+                config(requirement)
+            ),
+        )
+        .unwrap();
+    };
+
+    let output = project.loot(&["sync"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    // Resolving again revalidates the cached page instead of downloading it
+    fs::remove_file(project.path().join("lootbox.lock")).unwrap();
+    let output = project.loot(&["sync", "--dry-run"]);
+    assert!(output.status.success(), "{:?}", output);
+    let requests = fs::read_to_string(&log).unwrap();
+    assert!(requests.contains("200 /internal-lib/\n"), "{}", requests);
+    assert!(requests.contains("304 /internal-lib/\n"), "{}", requests);
+
+    // Without the index resolving fails as a network error, and a new venv is made only from the cache
+    drop(server);
+    let output = project.loot(&["sync", "--dry-run"]);
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("error: Error fetching"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    let output = project.loot(&["--offline", "sync", "--dry-run"]);
+    assert!(output.status.success(), "{:?}", output);
+    project.create_venv();
+    fs::remove_file(project.path().join(".lootbox").join("lootbox.lock")).unwrap();
+    let output = project.loot(&["--offline", "sync"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("+ internal-lib 2.0.0"));
+    let output = project.venv_python("import internal_lib");
+    assert!(output.status.success(), "{:?}", output);
+
+    // Version 1.0.0 was never downloaded
+    write_config("<2");
+    let output = project.loot(&["--offline", "sync", "--dry-run"]);
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("is not in the cache"), "{}", stderr);
+    assert!(stderr.contains("hint: Run without --offline"), "{}", stderr);
+}
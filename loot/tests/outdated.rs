@@ -0,0 +1,112 @@
+use std::fs;
+
+mod common;
+
+use common::{project_with_index, stdout, write_html_page, write_index, write_wheel};
+
+#[test]
+fn outdated_report() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &["app-utils"]),
+            ("app-lib", "2.0.0", &["app-utils"]),
+        ],
+    );
+    // The newest app-utils needs another python
+    let utils = index.path().join("app-utils");
+    write_html_page(
+        index.path(),
+        "app-utils",
+        &[
+            (write_wheel(&utils, "app-utils", "1.0.0", &[]), ""),
+            (
+                write_wheel(&utils, "app-utils", "1.1.0", &[]),
+                "data-requires-python=\"&gt;=99\"",
+            ),
+        ],
+    );
+    let project = project_with_index(index.path(), "app-lib = \"1.0.0\"\n");
+
+    let output = project.loot(&["outdated"]);
+    assert!(output.status.success(), "{:?}", output);
+    let table = stdout(&output);
+    assert!(
+        table.contains("Package    Current  Compatible  Latest  Type\n"),
+        "{}",
+        table
+    );
+    assert!(table.contains("app-lib    1.0.0    2.0.0       2.0.0   direct"));
+    assert!(table.contains("app-utils  1.0.0    1.0.0       1.1.0   transitive"));
+
+    let output = project.loot(&["outdated", "--format", "json"]);
+    assert!(output.status.success(), "{:?}", output);
+    let json = stdout(&output);
+    let packages: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(packages[0]["name"], "app-lib");
+    assert_eq!(packages[0]["compatible"], "2.0.0");
+    assert_eq!(packages[0]["direct"], true);
+    assert_eq!(packages[1]["latest"], "1.1.0");
+    assert_eq!(packages[1]["direct"], false);
+
+    let output = project.loot(&["outdated", "--exit-code"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    // A version for another python is reported but does not fail the check
+    fs::write(
+        project.path().join("lootbox.toml"),
+        fs::read_to_string(project.path().join("lootbox.toml"))
+            .unwrap()
+            .replace("app-lib = \"1.0.0\"", "app-lib = \"2.0.0\""),
+    )
+    .unwrap();
+    let output = project.loot(&["outdated", "--exit-code"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!stdout(&output).contains("app-lib "));
+}
+
+#[test]
+fn installed_versions_come_from_the_venv() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &[]),
+            ("app-lib", "1.1.0", &[]),
+            ("app-lib", "2.0.0", &[]),
+        ],
+    );
+    let project = project_with_index(index.path(), "app-lib = \"1.0.0\"\n");
+    let output = project.loot(&["sync"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    // Changed behind the back of loot, what runs is still what is in the venv
+    let wheel = index
+        .path()
+        .join("app-lib")
+        .join("app_lib-1.1.0-py3-none-any.whl");
+    let output = project.loot(&[
+        "exec",
+        "python",
+        "-m",
+        "pip",
+        "install",
+        "--no-deps",
+        "--no-index",
+        &wheel.to_string_lossy(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = project.loot(&["outdated", "--installed", "--format", "json"]);
+    assert!(output.status.success(), "{:?}", output);
+    let packages: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(packages.as_array().unwrap().len(), 1, "{}", packages);
+    assert_eq!(packages[0]["name"], "app-lib");
+    assert_eq!(packages[0]["current"], "1.1.0");
+    assert_eq!(packages[0]["latest"], "2.0.0");
+
+    let output = project.loot(&["outdated", "--format", "json"]);
+    let packages: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(packages[0]["current"], "1.0.0");
+}
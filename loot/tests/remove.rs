@@ -0,0 +1,42 @@
+use std::fs;
+
+mod common;
+
+use common::{project_with_index, write_index};
+
+#[test]
+fn remove_updates_the_lock_and_the_venv() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &["app-utils>=1"]),
+            ("app-utils", "1.0.0", &[]),
+            ("other-lib", "1.0.0", &[]),
+        ],
+    );
+    let project = project_with_index(index.path(), "app-lib = \">=1\"\nother-lib = \"*\"\n");
+
+    let output = project.loot(&["sync"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    // Names are matched however they are written
+    let output = project.loot(&["remove", "App_Lib"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let config = fs::read_to_string(project.path().join("lootbox.toml")).unwrap();
+    assert!(!config.contains("app-lib"), "{}", config);
+    let lock = fs::read_to_string(project.path().join("lootbox.lock")).unwrap();
+    assert!(!lock.contains("app-lib"), "{}", lock);
+    assert!(!lock.contains("app-utils"), "{}", lock);
+    assert!(lock.contains("other-lib"), "{}", lock);
+
+    let output = project.loot(&["sync", "--check"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!project.venv_python("import app_lib").status.success());
+    assert!(!project.venv_python("import app_utils").status.success());
+    assert!(project.venv_python("import other_lib").status.success());
+
+    let output = project.loot(&["remove", "app-lib"]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+}
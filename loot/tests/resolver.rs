@@ -0,0 +1,118 @@
+use std::fs;
+
+mod common;
+
+use common::{project_with_index, stdout, write_index};
+
+#[test]
+fn backtracks_extras_and_markers() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            (
+                "app-lib",
+                "1.0.0",
+                &["app-utils>=1", "app-plugins; extra == 'full'"],
+            ),
+            ("app-lib", "2.0.0", &["app-utils<1"]),
+            (
+                "app-plugins",
+                "1.0.0",
+                &["windows-only; sys_platform == 'win32'"],
+            ),
+            ("app-utils", "0.9.0", &[]),
+            ("app-utils", "1.0.0", &[]),
+            ("app-utils", "1.1.0", &[]),
+            ("app-utils", "2.0.0b1", &[]),
+            ("windows-only", "1.0.0", &[]),
+        ],
+    );
+
+    let project = project_with_index(
+        index.path(),
+        "app-lib = { version = \"*\", extras = [\"full\"] }\napp-utils = \">=1\"\n",
+    );
+
+    // app-lib 2.0.0 conflicts with the project, so the solver goes back to 1.0.0
+    let output = project.loot(&["sync"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = stdout(&output);
+    assert!(stdout.contains("+ app-lib 1.0.0"), "{}", stdout);
+    assert!(stdout.contains("+ app-utils 1.1.0"), "{}", stdout);
+    assert!(stdout.contains("+ app-plugins 1.0.0"), "{}", stdout);
+    assert!(!stdout.contains("windows-only"), "{}", stdout);
+
+    let lock = fs::read_to_string(project.path().join("lootbox.lock")).unwrap();
+    assert!(lock.contains("dependencies = [\n    \"app-plugins\",\n    \"app-utils\",\n]"));
+}
+
+#[test]
+fn explains_conflicts() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &["app-utils<1"]),
+            ("app-lib", "2.0.0", &["app-utils<1"]),
+            ("app-utils", "0.9.0", &[]),
+            ("app-utils", "1.2.0", &[]),
+        ],
+    );
+
+    let project = project_with_index(index.path(), "app-lib = \"*\"\napp-utils = \"1.2.0\"\n");
+
+    let output = project.loot(&["sync", "--dry-run"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("app-lib 2.0.0 depends on app-utils <1"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("the project depends on app-utils ==1.2.0"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("version solving failed"), "{}", stderr);
+
+    // The conflict is summed up with the entries of lootbox.toml to change
+    assert!(
+        stderr.contains("app-lib requires app-utils <1, but the project requires app-utils ==1.2.0 (requirements.app-utils)"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("requirements.app-lib = app-lib\n"));
+    assert!(stderr.contains("Try relaxing requirements.app-utils, it pins a single version"));
+    assert!(!stderr.contains("Try relaxing requirements.app-lib"));
+
+    let output = project.loot(&["--error-format", "json", "sync", "--dry-run"]);
+    assert!(!output.status.success());
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    let conflict = &error["conflicts"][0];
+    assert_eq!(conflict["package"], "app-utils");
+    assert_eq!(conflict["required_by"]["package"], "app-lib");
+    assert_eq!(conflict["required_by"]["requirement"], "app-utils <1");
+    assert_eq!(
+        conflict["conflicts_with"]["entry"],
+        "requirements.app-utils"
+    );
+    assert_eq!(error["entries"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn invalid_requirements_of_a_package_are_reported() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(index.path(), &[("app-lib", "1.0.0", &["[broken"])]);
+    let project = project_with_index(index.path(), "app-lib = \"*\"\n");
+
+    let output = project.loot(&["sync", "--dry-run"]);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("app-lib 1.0.0 has an invalid requirement [broken"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,80 @@
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+mod common;
+
+use common::Project;
+
+/// Starts `loot run` and waits until the program says it started
+fn start(project: &Project, args: &[&str], main: &str) -> Child {
+    std::fs::write(project.path().join("src").join("main.py"), main).unwrap();
+
+    let mut child = project
+        .loot_command(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let started = BufReader::new(child.stdout.take().unwrap())
+        .lines()
+        .any(|line| line.is_ok_and(|line| line.contains("started")));
+    if !started {
+        let _ = child.kill();
+        child.wait().unwrap();
+        panic!("The program did not start");
+    }
+    child
+}
+
+fn send(child: &Child, signal: &str) {
+    let status = Command::new("kill")
+        .args([signal, &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn interrupt_reaches_the_program() {
+    let project = Project::new("");
+    let mut child = start(
+        &project,
+        &["run"],
+        "import time\ntry:\n    print('started', flush=True)\n    time.sleep(60)\nfinally:\n    open('finally.txt', 'w').write('ran')\n",
+    );
+
+    send(&child, "-INT");
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(128 + 2));
+    assert_eq!(
+        std::fs::read_to_string(project.path().join("finally.txt")).unwrap(),
+        "ran"
+    );
+}
+
+#[test]
+fn program_is_killed_after_the_grace_period() {
+    let project = Project::new("");
+    let mut child = start(
+        &project,
+        &["--grace-period", "1", "run"],
+        "import os, signal, time\nsignal.signal(signal.SIGTERM, signal.SIG_IGN)\nopen('pid.txt', 'w').write(str(os.getpid()))\nprint('started', flush=True)\ntime.sleep(60)\n",
+    );
+
+    let start = Instant::now();
+    send(&child, "-TERM");
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(128 + 15));
+    assert!(start.elapsed() < Duration::from_secs(10));
+
+    // The program was killed too, not left running on its own
+    let pid = std::fs::read_to_string(project.path().join("pid.txt")).unwrap();
+    let alive = Command::new("kill")
+        .args(["-0", &pid])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!alive.success());
+}
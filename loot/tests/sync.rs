@@ -0,0 +1,50 @@
+use std::fs;
+
+mod common;
+
+use common::{project_with_index, stdout, write_index};
+
+#[test]
+fn check_and_dry_run_only_report() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[("app-lib", "1.0.0", &[]), ("other-lib", "1.0.0", &[])],
+    );
+    let project = project_with_index(index.path(), "app-lib = \"*\"\n");
+    let lock = project.path().join("lootbox.lock");
+
+    let output = project.loot(&["sync", "--check"]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(stdout(&output).contains("lootbox.lock is out of date"));
+    assert!(stdout(&output).contains("+ app-lib 1.0.0"));
+    assert!(!lock.exists());
+
+    let output = project.loot(&["sync", "--dry-run"]);
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert!(stdout(&output).contains("+ app-lib 1.0.0"));
+    assert!(!lock.exists());
+    assert!(!project.venv_python("import app_lib").status.success());
+
+    let output = project.loot(&["sync"]);
+    assert!(output.status.success(), "{:?}", output);
+    let output = project.loot(&["sync", "--check"]);
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert!(stdout(&output).contains("Venv is up to date"));
+
+    // A changed config makes the lock out of date even if the venv has everything
+    let config = fs::read_to_string(project.path().join("lootbox.toml")).unwrap();
+    let locked = fs::read_to_string(&lock).unwrap();
+    fs::write(
+        project.path().join("lootbox.toml"),
+        config.replace("app-lib = \"*\"", "app-lib = \"*\"\nother-lib = \"*\""),
+    )
+    .unwrap();
+    let output = project.loot(&["sync", "--check"]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(stdout(&output).contains("+ other-lib 1.0.0"));
+    let output = project.loot(&["sync", "--dry-run"]);
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert_eq!(fs::read_to_string(&lock).unwrap(), locked);
+    assert!(!project.venv_python("import other_lib").status.success());
+}
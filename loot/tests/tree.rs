@@ -0,0 +1,94 @@
+mod common;
+
+use common::{project_with_index, stdout, write_index, Project};
+
+fn project() -> (tempfile::TempDir, Project) {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &["app-utils", "app-core"]),
+            ("app-utils", "1.0.0", &["app-core"]),
+            ("app-core", "1.0.0", &[]),
+            ("dev-tool", "1.0.0", &["app-core"]),
+        ],
+    );
+    let project = project_with_index(
+        index.path(),
+        "app-lib = \"*\"\n\n[groups.dev]\ndev-tool = \"*\"\n",
+    );
+
+    (index, project)
+}
+
+fn loot(project: &Project, args: &[&str]) -> String {
+    let output = project.loot(args);
+    assert!(output.status.success(), "{:?}", output);
+    stdout(&output)
+}
+
+#[test]
+fn tree() {
+    let (_index, project) = project();
+
+    let output = loot(&project, &["tree"]);
+    assert!(
+        output.ends_with(
+            "project
+└── app-lib 1.0.0
+    ├── app-core 1.0.0
+    └── app-utils 1.0.0
+        └── app-core 1.0.0
+project [dev]
+└── dev-tool 1.0.0
+    └── app-core 1.0.0
+"
+        ),
+        "{}",
+        output
+    );
+
+    let output = loot(&project, &["tree", "--depth", "1"]);
+    assert!(
+        output.ends_with("project\n└── app-lib 1.0.0\nproject [dev]\n└── dev-tool 1.0.0\n"),
+        "{}",
+        output
+    );
+
+    // Packages already expanded are not repeated
+    let output = loot(&project, &["tree", "app-core", "--invert"]);
+    assert!(
+        output.ends_with(
+            "app-core 1.0.0
+├── app-lib 1.0.0
+│   └── project
+├── app-utils 1.0.0
+│   └── app-lib 1.0.0 (*)
+└── dev-tool 1.0.0
+    └── project [dev]
+"
+        ),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn why() {
+    let (_index, project) = project();
+
+    let output = loot(&project, &["why", "app-core"]);
+    assert!(
+        output.ends_with(
+            "project -> app-lib 1.0.0 -> app-core 1.0.0
+project -> app-lib 1.0.0 -> app-utils 1.0.0 -> app-core 1.0.0
+project [dev] -> dev-tool 1.0.0 -> app-core 1.0.0
+"
+        ),
+        "{}",
+        output
+    );
+
+    let output = project.loot(&["why", "missing-lib"]);
+    assert!(!output.status.success());
+}
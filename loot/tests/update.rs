@@ -0,0 +1,125 @@
+use std::fs;
+
+mod common;
+
+use common::{project_with_index, stdout, write_index};
+
+const RELEASES: [(&str, &str, &[&str]); 2] = [
+    ("app-lib", "1.0.0", &["app-utils>=1"]),
+    ("app-utils", "1.0.0", &[]),
+];
+const NEW_RELEASES: [(&str, &str, &[&str]); 2] = [
+    ("app-lib", "2.0.0", &["app-utils>=1"]),
+    ("app-utils", "1.1.0", &[]),
+];
+
+fn locked_version(lock: &str, package: &str) -> String {
+    let start = lock
+        .find(&format!("name = \"{}\"\nversion = \"", package))
+        .unwrap_or_else(|| panic!("{} is not locked", package))
+        + format!("name = \"{}\"\nversion = \"", package).len();
+    lock[start..start + lock[start..].find('"').unwrap()].to_owned()
+}
+
+#[test]
+fn update_keeps_other_packages() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(index.path(), &RELEASES);
+    let project = project_with_index(index.path(), "app-lib = \">=1\"\n");
+    let lock = || fs::read_to_string(project.path().join("lootbox.lock")).unwrap();
+
+    let output = project.loot(&["update"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(locked_version(&lock(), "app-lib"), "1.0.0");
+
+    // New releases are not picked up until the packages are updated
+    write_index(index.path(), &[RELEASES, NEW_RELEASES].concat());
+    let output = project.loot(&["sync", "--dry-run"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("+ app-lib 1.0.0"));
+
+    let output = project.loot(&["update", "app-lib"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("Package  Before  After\n"));
+    assert!(stdout(&output).contains("app-lib  1.0.0   2.0.0"));
+    assert_eq!(locked_version(&lock(), "app-lib"), "2.0.0");
+    assert_eq!(locked_version(&lock(), "app-utils"), "1.0.0");
+
+    let output = project.loot(&["update"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("app-utils  1.0.0   1.1.0"));
+
+    let output = project.loot(&["update"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("Everything is up to date"));
+
+    let output = project.loot(&["update", "missing-lib"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn upgrade_latest_rewrites_requirements() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(index.path(), &[RELEASES, NEW_RELEASES].concat());
+    let project = project_with_index(
+        index.path(),
+        "app-lib = \"1.0.0\"\napp-utils = \">=1.0.0\"\n",
+    );
+
+    let output = project.loot(&["upgrade", "--latest", "app-lib"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = fs::read_to_string(project.path().join("lootbox.toml")).unwrap();
+    assert!(config.contains("app-lib = \"2.0.0\""), "{}", config);
+    assert!(config.contains("app-utils = \">=1.0.0\""), "{}", config);
+
+    let output = project.loot(&["upgrade", "--latest"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = fs::read_to_string(project.path().join("lootbox.toml")).unwrap();
+    assert!(config.contains("app-utils = \">=1.1.0\""), "{}", config);
+    let lock = fs::read_to_string(project.path().join("lootbox.lock")).unwrap();
+    assert_eq!(locked_version(&lock, "app-lib"), "2.0.0");
+    assert_eq!(locked_version(&lock, "app-utils"), "1.1.0");
+
+    // The bound loot add writes keeps its shape
+    fs::write(
+        project.path().join("lootbox.toml"),
+        config.replace("app-lib = \"2.0.0\"", "app-lib = \">=1.0.0,<2\""),
+    )
+    .unwrap();
+    let output = project.loot(&["upgrade", "--latest", "app-lib"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = fs::read_to_string(project.path().join("lootbox.toml")).unwrap();
+    assert!(config.contains("app-lib = \">=2.0.0,<3\""), "{}", config);
+}
+
+#[test]
+fn upgrade_latest_keeps_ranges_and_config_on_conflict() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &["app-utils>=1"]),
+            ("app-lib", "2.0.0", &["app-utils>=2"]),
+            ("app-utils", "1.0.0", &[]),
+        ],
+    );
+    let project = project_with_index(index.path(), "app-lib = \"<2\"\napp-utils = \"1.0.0\"\n");
+
+    // Ranges it does not know how to move are left as they are
+    let output = project.loot(&["upgrade", "--latest", "app-lib"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("app-lib = \"<2\" is kept"));
+    let config = fs::read_to_string(project.path().join("lootbox.toml")).unwrap();
+    assert!(config.contains("app-lib = \"<2\""), "{}", config);
+
+    // A requirement that can not be resolved is not written
+    fs::write(
+        project.path().join("lootbox.toml"),
+        config.replace("app-lib = \"<2\"", "app-lib = \"1.0.0\""),
+    )
+    .unwrap();
+    let output = project.loot(&["upgrade", "--latest", "app-lib"]);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    let config = fs::read_to_string(project.path().join("lootbox.toml")).unwrap();
+    assert!(config.contains("app-lib = \"1.0.0\""), "{}", config);
+}
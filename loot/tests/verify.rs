@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+mod common;
+
+use common::{project_with_index, stdout, write_index, write_wheel};
+
+const RELEASES: [(&str, &str, &[&str]); 2] = [
+    ("app-lib", "1.0.0", &["app-utils>=1"]),
+    ("app-utils", "1.0.0", &[]),
+];
+
+#[test]
+fn verify_reports_changed_files() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(index.path(), &RELEASES);
+    let project = project_with_index(index.path(), "app-lib = \">=1\"\n");
+
+    let output = project.loot(&["sync"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = project.loot(&["verify"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("match their RECORD"));
+
+    let module = project.venv_python("import app_lib; print(app_lib.__file__)");
+    let module = stdout(&module).trim().to_owned();
+    fs::write(&module, "import os\n").unwrap();
+
+    let output = project.loot(&["verify"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("app_lib 1.0.0  app_lib/__init__.py  modified"));
+    assert!(!stdout(&output).contains("app_utils"));
+
+    fs::remove_file(&module).unwrap();
+    let output = project.loot(&["verify"]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("app_lib/__init__.py  missing"));
+}
+
+#[test]
+fn install_fails_when_download_does_not_match_lock() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(index.path(), &RELEASES);
+    let project = project_with_index(index.path(), "app-lib = \">=1\"\n");
+
+    // Locked without installing, so nothing is in the wheel cache yet
+    let output = project.loot(&["update"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    // The index now serves another file for the same version, with a page that agrees with it
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &["app-utils>=1", "other-lib"]),
+            ("app-utils", "1.0.0", &[]),
+        ],
+    );
+
+    let output = project.loot(&["sync"]);
+    assert!(!output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error installing app-lib"));
+
+    let import = project.venv_python("import app_lib");
+    assert!(!import.status.success());
+}
+
+#[test]
+fn install_fails_when_cached_wheel_does_not_match_lock() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(index.path(), &RELEASES);
+    let project = project_with_index(index.path(), "app-lib = \">=1\"\n");
+
+    let output = project.loot(&["sync"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    // The wheel in the cache is replaced by another one with the same name
+    let cached = find_file(
+        &project.root.path().join("data"),
+        "app_lib-1.0.0-py3-none-any.whl",
+    )
+    .expect("The wheel is cached");
+    let other = tempfile::tempdir().unwrap();
+    let replacement = write_wheel(other.path(), "app-lib", "1.0.0", &["other-lib"]);
+    fs::copy(&replacement, &cached).unwrap();
+
+    let output = project.loot(&["exec", "pip", "uninstall", "-y", "app-lib"]);
+    assert!(output.status.success(), "{:?}", output);
+    let output = project.loot(&["sync"]);
+    assert!(!output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error installing app-lib"));
+}
+
+fn find_file(directory: &Path, name: &str) -> Option<PathBuf> {
+    fs::read_dir(directory).ok()?.find_map(|entry| {
+        let path = entry.ok()?.path();
+        if path.is_dir() {
+            find_file(&path, name)
+        } else {
+            (path.file_name()? == name).then_some(path)
+        }
+    })
+}
@@ -0,0 +1,129 @@
+use std::fs;
+
+mod common;
+
+use common::{project_with_index, write_index, Project};
+
+fn lootbox_toml(project: &Project) -> String {
+    fs::read_to_string(project.path().join("lootbox.toml")).unwrap()
+}
+
+#[test]
+fn add_picks_newest_stable_version() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "9.0", &[]),
+            ("app-lib", "10.0", &[]),
+            ("app-lib", "11.0b1", &[]),
+            ("app-lib", "11.0.dev1", &[]),
+            ("beta-lib", "1.0a1", &[]),
+        ],
+    );
+    let project = project_with_index(index.path(), "");
+
+    let output = project.loot(&["add", "app-lib"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(lootbox_toml(&project).contains("app-lib = \">=10.0,<11\""));
+
+    let output = project.loot(&["add", "app-lib", "--pre"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(lootbox_toml(&project).contains("app-lib = \">=11.0b1,<12\""));
+
+    // Pre-releases asked for explicitly do not need --pre
+    let output = project.loot(&["add", "app-lib@11.0.dev1"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(lootbox_toml(&project).contains("app-lib = \"11.0.dev1\""));
+
+    let output = project.loot(&["add", "beta-lib"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--pre"));
+}
+
+#[test]
+fn add_bounds() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[("app-lib", "4.12.3", &[]), ("zero-lib", "0.27.1", &[])],
+    );
+    let project = project_with_index(index.path(), "");
+    let add = |args: &[&str], requirement: &str| {
+        let output = project.loot(&[&["add"], args].concat());
+        assert!(output.status.success(), "{:?}", output);
+        let config = lootbox_toml(&project);
+        assert!(config.contains(requirement), "{}", config);
+    };
+
+    add(&["app-lib"], "app-lib = \">=4.12.3,<5\"");
+    add(&["zero-lib"], "zero-lib = \">=0.27.1,<0.28\"");
+    add(&["app-lib", "--bound", "exact"], "app-lib = \"4.12.3\"");
+    add(
+        &["app-lib", "--bound", "compatible"],
+        "app-lib = \"~=4.12.3\"",
+    );
+    add(&["app-lib", "--bound", "lower"], "app-lib = \">=4.12.3\"");
+
+    // Explicit versions are pinned unless a bound is given too
+    add(&["app-lib@4.12.3"], "app-lib = \"4.12.3\"");
+    add(
+        &["app-lib@4.12.3", "--bound", "major"],
+        "app-lib = \">=4.12.3,<5\"",
+    );
+    let output = project.loot(&["add", "app-lib@>=4", "--bound", "major"]);
+    assert!(!output.status.success());
+
+    fs::write(
+        project.path().join("lootbox.toml"),
+        format!("bound = \"compatible\"\n{}", lootbox_toml(&project)),
+    )
+    .unwrap();
+    add(&["zero-lib"], "zero-lib = \"~=0.27.1\"");
+}
+
+#[test]
+fn resolution_strategies() {
+    let index = tempfile::tempdir().unwrap();
+    write_index(
+        index.path(),
+        &[
+            ("app-lib", "1.0.0", &["app-utils>=1"]),
+            ("app-lib", "2.0.0", &["app-utils>=1"]),
+            ("app-utils", "1.0.0", &[]),
+            ("app-utils", "1.1.0", &[]),
+        ],
+    );
+    let project = project_with_index(index.path(), "app-lib = \">=1\"\n");
+    let set_resolution = |resolution: &str| {
+        let config: String = lootbox_toml(&project)
+            .lines()
+            .filter(|line| !line.starts_with("resolution"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        fs::write(
+            project.path().join("lootbox.toml"),
+            format!("resolution = \"{}\"\n{}\n", resolution, config),
+        )
+        .unwrap();
+    };
+    let installed = |project: &Project| {
+        let output = project.loot(&["sync", "--dry-run"]);
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let output = installed(&project);
+    assert!(output.contains("+ app-lib 2.0.0"), "{}", output);
+    assert!(output.contains("+ app-utils 1.1.0"), "{}", output);
+
+    set_resolution("lowest");
+    let output = installed(&project);
+    assert!(output.contains("+ app-lib 1.0.0"), "{}", output);
+    assert!(output.contains("+ app-utils 1.0.0"), "{}", output);
+
+    set_resolution("lowest-direct");
+    let output = installed(&project);
+    assert!(output.contains("+ app-lib 1.0.0"), "{}", output);
+    assert!(output.contains("+ app-utils 1.1.0"), "{}", output);
+}